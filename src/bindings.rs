@@ -0,0 +1,348 @@
+use std::{fs, io};
+
+use bevy::{prelude::*, utils::HashMap};
+
+const BINDINGS_FILE: &str = "bindings.txt";
+
+/// Keys that can be bound, by name in the bindings file
+const KEYS: &[KeyCode] = &[
+    KeyCode::A,
+    KeyCode::B,
+    KeyCode::C,
+    KeyCode::D,
+    KeyCode::E,
+    KeyCode::F,
+    KeyCode::G,
+    KeyCode::H,
+    KeyCode::I,
+    KeyCode::J,
+    KeyCode::K,
+    KeyCode::L,
+    KeyCode::M,
+    KeyCode::N,
+    KeyCode::O,
+    KeyCode::P,
+    KeyCode::Q,
+    KeyCode::R,
+    KeyCode::S,
+    KeyCode::T,
+    KeyCode::U,
+    KeyCode::V,
+    KeyCode::W,
+    KeyCode::X,
+    KeyCode::Y,
+    KeyCode::Z,
+    KeyCode::Key0,
+    KeyCode::Key1,
+    KeyCode::Key2,
+    KeyCode::Key3,
+    KeyCode::Key4,
+    KeyCode::Key5,
+    KeyCode::Key6,
+    KeyCode::Key7,
+    KeyCode::Key8,
+    KeyCode::Key9,
+    KeyCode::Left,
+    KeyCode::Right,
+    KeyCode::Up,
+    KeyCode::Down,
+    KeyCode::Space,
+    KeyCode::Return,
+    KeyCode::Tab,
+    KeyCode::Back,
+    KeyCode::LShift,
+    KeyCode::RShift,
+    KeyCode::LControl,
+    KeyCode::RControl,
+    KeyCode::LAlt,
+    KeyCode::RAlt,
+    KeyCode::Comma,
+    KeyCode::Period,
+    KeyCode::Slash,
+    KeyCode::Semicolon,
+    KeyCode::Apostrophe,
+    KeyCode::LBracket,
+    KeyCode::RBracket,
+    KeyCode::Minus,
+    KeyCode::Equals,
+];
+
+/// Gamepad buttons that can be bound, by name in the bindings file
+const BUTTONS: &[GamepadButtonType] = &[
+    GamepadButtonType::South,
+    GamepadButtonType::East,
+    GamepadButtonType::North,
+    GamepadButtonType::West,
+    GamepadButtonType::C,
+    GamepadButtonType::Z,
+    GamepadButtonType::LeftTrigger,
+    GamepadButtonType::LeftTrigger2,
+    GamepadButtonType::RightTrigger,
+    GamepadButtonType::RightTrigger2,
+    GamepadButtonType::Select,
+    GamepadButtonType::Start,
+    GamepadButtonType::Mode,
+    GamepadButtonType::LeftThumb,
+    GamepadButtonType::RightThumb,
+    GamepadButtonType::DPadUp,
+    GamepadButtonType::DPadDown,
+    GamepadButtonType::DPadLeft,
+    GamepadButtonType::DPadRight,
+];
+
+/// What the player can do, independent of the key or button doing it
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Action {
+    MoveLeft,
+    MoveRight,
+    MoveUp,
+    MoveDown,
+    Fire,
+    SwitchWeapon,
+    Pause,
+}
+
+impl Action {
+    pub const ALL: [Action; 7] = [
+        Action::MoveLeft,
+        Action::MoveRight,
+        Action::MoveUp,
+        Action::MoveDown,
+        Action::Fire,
+        Action::SwitchWeapon,
+        Action::Pause,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Action::MoveLeft => "Move Left",
+            Action::MoveRight => "Move Right",
+            Action::MoveUp => "Move Up",
+            Action::MoveDown => "Move Down",
+            Action::Fire => "Fire",
+            Action::SwitchWeapon => "Switch Weapon",
+            Action::Pause => "Pause",
+        }
+    }
+}
+
+/// Keys and gamepad buttons bound to one action, the first of each is the one rebinding replaces
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Binding {
+    pub keys: Vec<KeyCode>,
+    pub buttons: Vec<GamepadButtonType>,
+}
+
+// region: --- Resources
+
+/// Resource - Input bindings, kept in `bindings.txt`, e.g.
+/// ```text
+/// MoveLeft A Left | DPadLeft
+/// Fire Space | South RightTrigger
+/// ```
+/// with the keys before the `|` and the gamepad buttons after it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Bindings(HashMap<Action, Binding>);
+
+impl Default for Bindings {
+    fn default() -> Self {
+        let binding = |keys: &[KeyCode], buttons: &[GamepadButtonType]| Binding {
+            keys: keys.to_vec(),
+            buttons: buttons.to_vec(),
+        };
+
+        Self(HashMap::from_iter([
+            (
+                Action::MoveLeft,
+                binding(&[KeyCode::A, KeyCode::Left], &[GamepadButtonType::DPadLeft]),
+            ),
+            (
+                Action::MoveRight,
+                binding(
+                    &[KeyCode::D, KeyCode::Right],
+                    &[GamepadButtonType::DPadRight],
+                ),
+            ),
+            (
+                Action::MoveUp,
+                binding(&[KeyCode::W, KeyCode::Up], &[GamepadButtonType::DPadUp]),
+            ),
+            (
+                Action::MoveDown,
+                binding(&[KeyCode::S, KeyCode::Down], &[GamepadButtonType::DPadDown]),
+            ),
+            (
+                Action::Fire,
+                binding(
+                    &[KeyCode::Space],
+                    &[GamepadButtonType::South, GamepadButtonType::RightTrigger],
+                ),
+            ),
+            (
+                Action::SwitchWeapon,
+                binding(&[KeyCode::Q], &[GamepadButtonType::West]),
+            ),
+            (
+                Action::Pause,
+                binding(&[KeyCode::P], &[GamepadButtonType::Start]),
+            ),
+        ]))
+    }
+}
+
+impl Bindings {
+    /// Loads the saved bindings on top of the defaults; a missing file keeps the defaults.
+    pub fn load() -> Self {
+        let mut bindings = Self::default();
+        if let Ok(text) = fs::read_to_string(BINDINGS_FILE) {
+            for line in text.lines().filter(|line| !line.trim().is_empty()) {
+                if let Err(err) = bindings.parse_line(line) {
+                    eprintln!("error: {}: {}", BINDINGS_FILE, err);
+                }
+            }
+        }
+        bindings
+    }
+
+    pub fn save(&self) -> io::Result<()> {
+        let mut text = String::new();
+        for action in Action::ALL {
+            let binding = self.get(action);
+            let keys = binding.keys.iter().map(|key| format!(" {:?}", key));
+            let buttons = binding
+                .buttons
+                .iter()
+                .map(|button| format!(" {:?}", button));
+            text += &format!(
+                "{:?}{} |{}\n",
+                action,
+                keys.collect::<String>(),
+                buttons.collect::<String>()
+            );
+        }
+        fs::write(BINDINGS_FILE, text)
+    }
+
+    fn parse_line(&mut self, line: &str) -> Result<(), String> {
+        let (name, rest) = line.trim().split_once(' ').unwrap_or((line.trim(), ""));
+        let action = Action::ALL
+            .into_iter()
+            .find(|action| format!("{:?}", action) == name)
+            .ok_or_else(|| format!("unknown action `{}`", name))?;
+        let (keys, buttons) = rest.split_once('|').unwrap_or((rest, ""));
+
+        let binding = Binding {
+            keys: keys
+                .split_whitespace()
+                .map(parse_key)
+                .collect::<Result<_, _>>()?,
+            buttons: buttons
+                .split_whitespace()
+                .map(parse_button)
+                .collect::<Result<_, _>>()?,
+        };
+        self.0.insert(action, binding);
+        Ok(())
+    }
+
+    pub fn get(&self, action: Action) -> &Binding {
+        // every action is bound by `default`, and loading only replaces bindings
+        &self.0[&action]
+    }
+
+    /// Is a key or a button of the gamepad (if any) bound to the action held down?
+    pub fn pressed(
+        &self,
+        action: Action,
+        keyboard: &Input<KeyCode>,
+        gamepad: Option<Gamepad>,
+        buttons: &Input<GamepadButton>,
+    ) -> bool {
+        let binding = self.get(action);
+        keyboard.any_pressed(binding.keys.iter().copied())
+            || gamepad.is_some_and(|gamepad| {
+                binding
+                    .buttons
+                    .iter()
+                    .any(|&button_type| buttons.pressed(GamepadButton(gamepad, button_type)))
+            })
+    }
+
+    /// Was a key or a button of the gamepad (if any) bound to the action pressed this frame?
+    pub fn just_pressed(
+        &self,
+        action: Action,
+        keyboard: &Input<KeyCode>,
+        gamepad: Option<Gamepad>,
+        buttons: &Input<GamepadButton>,
+    ) -> bool {
+        let binding = self.get(action);
+        keyboard.any_just_pressed(binding.keys.iter().copied())
+            || gamepad.is_some_and(|gamepad| {
+                binding
+                    .buttons
+                    .iter()
+                    .any(|&button_type| buttons.just_pressed(GamepadButton(gamepad, button_type)))
+            })
+    }
+
+    /// Makes `key` the main key of the action, and takes it away from any other action.
+    pub fn bind_key(&mut self, action: Action, key: KeyCode) {
+        for binding in self.0.values_mut() {
+            binding.keys.retain(|&bound| bound != key);
+        }
+        let keys = &mut self.0.entry(action).or_default().keys;
+        match keys.first_mut() {
+            Some(first) => *first = key,
+            None => keys.push(key),
+        }
+    }
+
+    /// Makes `button` the main gamepad button of the action, and takes it away from any other action.
+    pub fn bind_button(&mut self, action: Action, button: GamepadButtonType) {
+        for binding in self.0.values_mut() {
+            binding.buttons.retain(|&bound| bound != button);
+        }
+        let buttons = &mut self.0.entry(action).or_default().buttons;
+        match buttons.first_mut() {
+            Some(first) => *first = button,
+            None => buttons.push(button),
+        }
+    }
+}
+
+// endregion: --- Resources
+
+/// The first bindable key pressed this frame, if any.
+pub fn just_pressed_key(keyboard: &Input<KeyCode>) -> Option<KeyCode> {
+    keyboard
+        .get_just_pressed()
+        .copied()
+        .find(|key| KEYS.contains(key))
+}
+
+/// The first bindable button of the gamepad pressed this frame, if any.
+pub fn just_pressed_button(
+    gamepad: Gamepad,
+    buttons: &Input<GamepadButton>,
+) -> Option<GamepadButtonType> {
+    BUTTONS
+        .iter()
+        .copied()
+        .find(|&button_type| buttons.just_pressed(GamepadButton(gamepad, button_type)))
+}
+
+fn parse_key(name: &str) -> Result<KeyCode, String> {
+    KEYS.iter()
+        .copied()
+        .find(|key| format!("{:?}", key) == name)
+        .ok_or_else(|| format!("unknown key `{}`", name))
+}
+
+fn parse_button(name: &str) -> Result<GamepadButtonType, String> {
+    BUTTONS
+        .iter()
+        .copied()
+        .find(|button| format!("{:?}", button) == name)
+        .ok_or_else(|| format!("unknown gamepad button `{}`", name))
+}
@@ -0,0 +1,543 @@
+use std::f32::consts::PI;
+
+use bevy::{math::Vec3Swizzles, prelude::*, sprite::collide_aabb::collide};
+use bevy_kira_audio::Audio;
+use serde::Deserialize;
+
+use crate::{
+    components::{
+        Armor, Attributes, Damage, Enemy, ExplosionToSpawn, FromEnemy, FromPlayer, Laser, Player,
+        SpriteSize,
+    },
+    enemy::{spawn_shot, wave_boss, BulletPattern, Gun, LevelRunner, Wave, WavePhase},
+    in_game,
+    weapon::Piercing,
+    EnemyCount, GameMode, GameRng, GameSounds, GameState, GameTextures, SimLabel, SimTime,
+    SimulationStage, WinSize, BASE_SPEED, TIME_STEP,
+};
+
+const BOSS_TOP: f32 = 160.; // from the top of the window to where the boss holds
+const BOSS_ENTRY_SPEED: f32 = 0.15; // multiplier of the base speed, while coming down
+const BOSS_SWAY_MARGIN: f32 = 20.; // from the edges of the window
+const BOSS_PHASE_PAUSE: f64 = 1.5; // seconds the boss holds its fire after switching phases
+
+/// Part of a boss a laser can hit
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ZoneKind {
+    /// takes the damage as it is
+    Hull,
+    /// takes `WEAK_MULTIPLIER` times the damage
+    Weak,
+    /// stops most of the damage, see `ARMORED_PLATE`
+    Armored,
+}
+
+const WEAK_MULTIPLIER: f32 = 3.;
+const ARMORED_PLATE: Armor = Armor {
+    resist: 0.8,
+    flat: 0.,
+};
+
+impl ZoneKind {
+    fn multiplier(&self) -> f32 {
+        match self {
+            ZoneKind::Weak => WEAK_MULTIPLIER,
+            ZoneKind::Hull | ZoneKind::Armored => 1.,
+        }
+    }
+
+    fn color(&self) -> Color {
+        match self {
+            ZoneKind::Hull => Color::NONE, // the boss sprite shows it
+            ZoneKind::Weak => Color::rgba(1., 0.2, 0.2, 0.8),
+            ZoneKind::Armored => Color::rgba(0.5, 0.5, 0.6, 0.9),
+        }
+    }
+}
+
+/// Hit zone of a boss: kind, offset from the center of the boss and size, in pixels
+pub struct ZoneDef(pub ZoneKind, pub (f32, f32), pub (f32, f32));
+
+/// Phase of a boss fight: the pattern fired from `from` (a fraction of the max health) down,
+/// with `interval` seconds between the volleys
+pub struct PhaseDef {
+    pub from: f32,
+    pub pattern: BulletPattern,
+    pub interval: f64,
+}
+
+/// Definition of a boss, see `BossKind::def`
+pub struct BossType {
+    pub name: &'static str,
+    pub sprite: &'static str,
+    pub size: (f32, f32), // of the sprite image
+    pub scale: f32,
+    pub color: Color,
+    pub health: f32,
+    pub score: u32,
+    pub sway_speed: f32, // radians per second of the side to side sway
+    pub zones: &'static [ZoneDef],
+    pub phases: &'static [PhaseDef], // by decreasing `from`, the first one from 1
+    pub laser_damage: f32,
+}
+
+const BOSS_TYPES: [BossType; 2] = [
+    BossType {
+        name: "mothership",
+        sprite: "enemy_a_01.png",
+        size: (144., 75.),
+        scale: 1.2,
+        color: Color::rgb(0.8, 0.6, 1.),
+        health: 600.,
+        score: 500,
+        sway_speed: 0.6,
+        zones: &[
+            ZoneDef(ZoneKind::Hull, (0., 10.), (160., 50.)),
+            ZoneDef(ZoneKind::Weak, (0., -30.), (30., 20.)),
+            ZoneDef(ZoneKind::Armored, (-55., -22.), (40., 25.)),
+            ZoneDef(ZoneKind::Armored, (55., -22.), (40., 25.)),
+        ],
+        phases: &[
+            PhaseDef {
+                from: 1.,
+                pattern: BulletPattern::Spread {
+                    count: 5,
+                    arc: PI / 3.,
+                    speed: 0.45,
+                    aimed: false,
+                },
+                interval: 1.2,
+            },
+            PhaseDef {
+                from: 0.6,
+                pattern: BulletPattern::Burst {
+                    count: 4,
+                    gap: 0.15,
+                    speed: 0.6,
+                    aimed: true,
+                },
+                interval: 1.5,
+            },
+            PhaseDef {
+                from: 0.3,
+                pattern: BulletPattern::Spiral {
+                    arms: 12,
+                    spin: PI / 12.,
+                    speed: 0.35,
+                },
+                interval: 0.9,
+            },
+        ],
+        laser_damage: 15.,
+    },
+    BossType {
+        name: "dreadnought",
+        sprite: "enemy_a_01.png",
+        size: (144., 75.),
+        scale: 1.5,
+        color: Color::rgb(1., 0.5, 0.3),
+        health: 1200.,
+        score: 1000,
+        sway_speed: 0.4,
+        zones: &[
+            ZoneDef(ZoneKind::Hull, (0., 15.), (200., 60.)),
+            ZoneDef(ZoneKind::Weak, (-40., -32.), (26., 18.)),
+            ZoneDef(ZoneKind::Weak, (40., -32.), (26., 18.)),
+            ZoneDef(ZoneKind::Armored, (0., -30.), (40., 40.)),
+            ZoneDef(ZoneKind::Armored, (-85., -20.), (40., 25.)),
+            ZoneDef(ZoneKind::Armored, (85., -20.), (40., 25.)),
+        ],
+        phases: &[
+            PhaseDef {
+                from: 1.,
+                pattern: BulletPattern::Spiral {
+                    arms: 10,
+                    spin: PI / 15.,
+                    speed: 0.35,
+                },
+                interval: 1.,
+            },
+            PhaseDef {
+                from: 0.7,
+                pattern: BulletPattern::Beam {
+                    arc: PI * 5. / 9.,
+                    period: 2.,
+                    gap: 0.06,
+                    speed: 0.5,
+                },
+                interval: 1.5,
+            },
+            PhaseDef {
+                from: 0.4,
+                pattern: BulletPattern::Burst {
+                    count: 5,
+                    gap: 0.12,
+                    speed: 0.7,
+                    aimed: true,
+                },
+                interval: 1.,
+            },
+            PhaseDef {
+                from: 0.15,
+                pattern: BulletPattern::Spread {
+                    count: 9,
+                    arc: PI * 2. / 3.,
+                    speed: 0.5,
+                    aimed: false,
+                },
+                interval: 0.6,
+            },
+        ],
+        laser_damage: 20.,
+    },
+];
+
+// region: --- Components
+
+/// Component - Type of a boss, the registry of boss types is `BOSS_TYPES`.
+/// Its health is in the `Attributes` of the boss, its hit zones are `BossZone`s.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Component, Deserialize)]
+pub enum BossKind {
+    Mothership,
+    Dreadnought,
+}
+
+impl BossKind {
+    pub const ALL: [BossKind; 2] = [BossKind::Mothership, BossKind::Dreadnought];
+
+    pub fn def(&self) -> &'static BossType {
+        &BOSS_TYPES[*self as usize]
+    }
+}
+
+/// Component - State of a boss fight, its `Gun` fires the pattern of the phase
+#[derive(Component)]
+pub struct Boss {
+    pub phase: usize,        // index in the `phases` of the boss
+    arrived_at: Option<f64>, // sim time the boss got to its place, it only fires from there
+}
+
+/// Component - Hit zone of a boss, kept at its offset from the boss
+#[derive(Component)]
+pub struct BossZone {
+    boss: Entity,
+    kind: ZoneKind,
+    offset: Vec2,
+}
+
+// endregion: --- Components
+
+/// Bosses fight at the end of the waves that have one, once the rest of the wave is down.
+pub struct BossPlugin;
+
+impl Plugin for BossPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system_set_to_stage(
+            SimulationStage,
+            SystemSet::new()
+                .with_run_criteria(in_game)
+                .label(SimLabel::Spawn)
+                .after(SimLabel::Clock)
+                .with_system(boss_spawn_system),
+        )
+        .add_system_set_to_stage(
+            SimulationStage,
+            SystemSet::new()
+                .with_run_criteria(in_game)
+                .label(SimLabel::Fire)
+                .after(SimLabel::Input)
+                .with_system(boss_fire_system),
+        )
+        .add_system_set_to_stage(
+            SimulationStage,
+            SystemSet::new()
+                .with_run_criteria(in_game)
+                .label(SimLabel::Movement)
+                .after(SimLabel::Fire)
+                .with_system(boss_movement_system),
+        )
+        .add_system_set_to_stage(
+            SimulationStage,
+            SystemSet::new()
+                .with_run_criteria(in_game)
+                .label(SimLabel::Collision)
+                .after(SimLabel::Movement)
+                .with_system(boss_hit_system),
+        );
+    }
+}
+
+/// Brings the boss of the wave in once the rest of the wave is down.
+/// It counts as an enemy of the wave, so the wave is only cleared with it.
+fn boss_spawn_system(
+    mut commands: Commands,
+    mut wave: ResMut<Wave>,
+    mut enemy_count: ResMut<EnemyCount>,
+    level_runner: Res<LevelRunner>,
+    game_mode: Res<GameMode>,
+    game_textures: Res<GameTextures>,
+    win_size: Res<WinSize>,
+) {
+    if wave.phase != WavePhase::Fighting || enemy_count.0 > 0 || wave.boss_spawned {
+        return;
+    }
+    let kind = match wave_boss(&wave, &level_runner, *game_mode) {
+        Some(kind) => kind,
+        None => return,
+    };
+
+    let def = kind.def();
+    let health = def.health * wave.difficulty();
+    let y = win_size.height / 2. + def.size.1 * def.scale;
+
+    let boss = commands
+        .spawn_bundle(SpriteBundle {
+            texture: game_textures.bosses.get(&kind).cloned().unwrap_or_default(),
+            sprite: Sprite {
+                color: def.color,
+                ..Default::default()
+            },
+            transform: Transform {
+                translation: Vec3::new(0., y, 10.),
+                scale: Vec3::new(def.scale, def.scale, 1.),
+                ..Default::default()
+            },
+            ..Default::default()
+        })
+        .insert(Enemy)
+        .insert(kind)
+        .insert(Boss {
+            phase: 0,
+            arrived_at: None,
+        })
+        .insert(Gun::new(0., 0.))
+        .insert(SpriteSize::from(def.size))
+        .insert(Attributes {
+            health,
+            max_health: health,
+        })
+        .id();
+
+    for &ZoneDef(zone_kind, offset, size) in def.zones {
+        let offset = Vec2::from(offset);
+        let zone = commands
+            .spawn_bundle(SpriteBundle {
+                sprite: Sprite {
+                    color: zone_kind.color(),
+                    custom_size: Some(size.into()),
+                    ..Default::default()
+                },
+                transform: Transform::from_xyz(offset.x, y + offset.y, 11.),
+                ..Default::default()
+            })
+            .insert(Enemy)
+            .insert(BossZone {
+                boss,
+                kind: zone_kind,
+                offset,
+            })
+            .insert(SpriteSize::from(size))
+            .id();
+        if zone_kind == ZoneKind::Armored {
+            commands.entity(zone).insert(ARMORED_PLATE);
+        }
+    }
+
+    enemy_count.0 += 1;
+    wave.boss_spawned = true;
+}
+
+/// Comes down to its place, then sways from side to side, with its zones in tow.
+fn boss_movement_system(
+    sim_time: Res<SimTime>,
+    win_size: Res<WinSize>,
+    mut boss_query: Query<(Entity, &mut Transform, &mut Boss, &BossKind), Without<BossZone>>,
+    mut zone_query: Query<(&mut Transform, &BossZone), Without<Boss>>,
+) {
+    let now = sim_time.elapsed;
+    let hold_y = win_size.height / 2. - BOSS_TOP;
+
+    for (boss_entity, mut transform, mut boss, kind) in boss_query.iter_mut() {
+        let def = kind.def();
+        let translation = &mut transform.translation;
+
+        match boss.arrived_at {
+            None => {
+                translation.y =
+                    (translation.y - BOSS_ENTRY_SPEED * BASE_SPEED * TIME_STEP).max(hold_y);
+                if translation.y <= hold_y {
+                    boss.arrived_at = Some(now);
+                }
+            }
+            Some(arrived_at) => {
+                let reach = win_size.width / 2. - def.size.0 * def.scale / 2. - BOSS_SWAY_MARGIN;
+                translation.x = reach.max(0.) * ((now - arrived_at) as f32 * def.sway_speed).sin();
+            }
+        }
+
+        let position = translation.xy();
+        for (mut zone_tf, zone) in zone_query
+            .iter_mut()
+            .filter(|(_, zone)| zone.boss == boss_entity)
+        {
+            (zone_tf.translation.x, zone_tf.translation.y) = (position + zone.offset).into();
+        }
+    }
+}
+
+/// Fires the pattern of the phase the health of the boss is in.
+fn boss_fire_system(
+    mut commands: Commands,
+    sim_time: Res<SimTime>,
+    wave: Res<Wave>,
+    mut rng: ResMut<GameRng>,
+    game_textures: Res<GameTextures>,
+    game_sounds: Res<GameSounds>,
+    mut boss_query: Query<(&Transform, &mut Boss, &mut Gun, &BossKind, &Attributes)>,
+    bullet_query: Query<(), (With<Laser>, With<FromEnemy>)>,
+    player_query: Query<&Transform, With<Player>>,
+    audio: Res<Audio>,
+) {
+    let now = sim_time.elapsed;
+    let player = player_query
+        .get_single()
+        .ok()
+        .map(|transform| transform.translation.xy());
+    // past the bullet cap of the wave, the boss holds its fire too
+    let capped = bullet_query.iter().count() >= wave.bullet_cap();
+
+    for (transform, mut boss, mut gun, kind, attributes) in boss_query.iter_mut() {
+        let def = kind.def();
+        if boss.arrived_at.is_none() {
+            continue;
+        }
+
+        // a new phase starts after a pause, from its first volley
+        let fraction = attributes.health / attributes.max_health;
+        let phase = def
+            .phases
+            .iter()
+            .rposition(|phase| fraction <= phase.from)
+            .unwrap_or(0);
+        if phase != boss.phase {
+            boss.phase = phase;
+            gun.reset(now + BOSS_PHASE_PAUSE);
+        }
+        if now < gun.next_shot || capped {
+            continue;
+        }
+
+        let PhaseDef {
+            pattern, interval, ..
+        } = def.phases[phase];
+        let interval = interval / wave.difficulty() as f64;
+        let origin = transform.translation.xy() - Vec2::new(0., def.size.1 * def.scale / 2.);
+        gun.fire(
+            pattern,
+            origin,
+            player,
+            &sim_time,
+            interval,
+            &mut rng.0,
+            |shot| {
+                spawn_shot(
+                    &mut commands,
+                    &game_textures,
+                    &sim_time,
+                    origin,
+                    shot,
+                    def.laser_damage,
+                );
+            },
+        );
+
+        audio.play(game_sounds.enemy_laser.clone());
+    }
+}
+
+/// Takes the player lasers on the first zone in their way: weak points take extra damage,
+/// armored parts stop most of it. The boss goes down when its health runs out.
+fn boss_hit_system(
+    mut commands: Commands,
+    mut enemy_count: ResMut<EnemyCount>,
+    mut game_state: ResMut<GameState>,
+    mut laser_query: Query<
+        (
+            Entity,
+            &Transform,
+            &SpriteSize,
+            &Damage,
+            Option<&mut Piercing>,
+        ),
+        (With<Laser>, With<FromPlayer>),
+    >,
+    zone_query: Query<(Entity, &Transform, &SpriteSize, &BossZone, Option<&Armor>)>,
+    mut boss_query: Query<(&Transform, &BossKind, &mut Attributes), With<Boss>>,
+    game_sounds: Res<GameSounds>,
+    audio: Res<Audio>,
+) {
+    for (laser_entity, laser_tf, laser_size, damage, piercing) in laser_query.iter_mut() {
+        let laser_size = laser_size.0 * laser_tf.scale.xy();
+
+        // the player fires upwards, so the zone reaching down the furthest is hit first
+        let bottom =
+            |(_, tf, size, _, _): &(Entity, &Transform, &SpriteSize, &BossZone, Option<&Armor>)| {
+                tf.translation.y - size.0.y * tf.scale.y / 2.
+            };
+        let hit = zone_query
+            .iter()
+            .filter(|(_, zone_tf, zone_size, _, _)| {
+                collide(
+                    laser_tf.translation,
+                    laser_size,
+                    zone_tf.translation,
+                    zone_size.0 * zone_tf.scale.xy(),
+                )
+                .is_some()
+            })
+            .min_by(|a, b| bottom(a).total_cmp(&bottom(b)));
+        let (_, _, _, zone, armor) = match hit {
+            Some(hit) => hit,
+            None => continue,
+        };
+
+        // a beam goes on through the boss, and only damages it once
+        match piercing {
+            Some(piercing) if piercing.hit.contains(&zone.boss) => continue,
+            Some(mut piercing) => piercing.hit.push(zone.boss),
+            None => commands.entity(laser_entity).despawn(),
+        }
+
+        let (boss_tf, kind, mut attributes) = match boss_query.get_mut(zone.boss) {
+            // already down by an earlier laser of this tick
+            Ok(boss) if !boss.2.is_dead() => boss,
+            _ => continue,
+        };
+        attributes.take_damage(&Damage(damage.0 * zone.kind.multiplier()), armor);
+        if !attributes.is_dead() {
+            continue;
+        }
+
+        // the boss and its zones go down together, the weak points in explosions of their own
+        let def = kind.def();
+        commands.entity(zone.boss).despawn();
+        commands
+            .spawn()
+            .insert(ExplosionToSpawn(boss_tf.translation, def.scale * 2.));
+        for (zone_entity, zone_tf, _, other, _) in zone_query
+            .iter()
+            .filter(|(.., other, _)| other.boss == zone.boss)
+        {
+            commands.entity(zone_entity).despawn();
+            if other.kind == ZoneKind::Weak {
+                commands
+                    .spawn()
+                    .insert(ExplosionToSpawn(zone_tf.translation, 1.));
+            }
+        }
+        audio.play(game_sounds.player_explosion.clone());
+
+        enemy_count.0 -= 1;
+        game_state.score += def.score;
+    }
+}
@@ -0,0 +1,447 @@
+use bevy::{
+    math::Vec3Swizzles,
+    prelude::*,
+    render::render_resource::{Extent3d, FilterMode, TextureDimension, TextureFormat},
+    sprite::collide_aabb::collide,
+};
+
+use crate::{
+    components::{Laser, SpriteSize, Velocity},
+    enemy::Invader,
+    in_game, AppState, GameMode, SimLabel, SimulationStage, WinSize,
+};
+
+const BUNKER_COUNT: u32 = 4;
+const BUNKER_PIXEL: f32 = 3.; // screen pixels per pixel of the mask
+const BUNKER_BOTTOM: f32 = 90.; // from the bottom of the window to the bottom of the bunkers
+const BUNKER_COLOR: [u8; 4] = [50, 205, 50, 255];
+const CRATER_RADIUS: f32 = 2.5; // in pixels of the mask
+
+/// Solid pixels of a bunker, row by row from its top left pixel.
+/// Lasers and invaders erode it, and the bunker texture is redrawn from it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct BunkerMask {
+    width: usize,
+    height: usize,
+    solid: Vec<bool>,
+}
+
+impl BunkerMask {
+    /// A solid block
+    pub fn new(width: usize, height: usize) -> Self {
+        Self {
+            width,
+            height,
+            solid: vec![true; width * height],
+        }
+    }
+
+    /// The arcade shape: a block with its top corners cut off and an arch cut out of its bottom.
+    pub fn classic() -> Self {
+        const WIDTH: usize = 22;
+        const HEIGHT: usize = 16;
+        const CORNER: usize = 4; // pixels cut off the top corners, along both edges
+        const ARCH: (f32, f32) = (5., 6.); // half width and height
+
+        let mut mask = Self::new(WIDTH, HEIGHT);
+        for y in 0..HEIGHT {
+            for x in 0..WIDTH {
+                let from_side = x.min(WIDTH - 1 - x);
+                let corner = from_side + y < CORNER;
+                let (dx, dy) = (
+                    (x as f32 + 0.5 - WIDTH as f32 / 2.) / ARCH.0,
+                    (y as f32 + 0.5 - HEIGHT as f32) / ARCH.1,
+                );
+                let arch = dx * dx + dy * dy < 1.;
+                mask.solid[y * WIDTH + x] = !corner && !arch;
+            }
+        }
+        mask
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// Is the pixel solid? Pixels outside of the mask never are.
+    pub fn is_solid(&self, x: i32, y: i32) -> bool {
+        self.index(x, y).is_some_and(|index| self.solid[index])
+    }
+
+    pub fn solid_count(&self) -> usize {
+        self.solid.iter().filter(|solid| **solid).count()
+    }
+
+    /// The first solid pixel within the columns `x0..=x1` and the rows `y0..=y1`, going through the
+    /// rows from the top when `downward`, from the bottom otherwise, i.e. the pixel a shot traveling
+    /// that way hits first.
+    pub fn first_solid(
+        &self,
+        (x0, y0): (i32, i32),
+        (x1, y1): (i32, i32),
+        downward: bool,
+    ) -> Option<(i32, i32)> {
+        let (x0, x1) = (x0.max(0), x1.min(self.width as i32 - 1));
+        let (y0, y1) = (y0.max(0), y1.min(self.height as i32 - 1));
+        let rows: Box<dyn Iterator<Item = i32>> = if downward {
+            Box::new(y0..=y1)
+        } else {
+            Box::new((y0..=y1).rev())
+        };
+
+        for y in rows {
+            if let Some(x) = (x0..=x1).find(|&x| self.is_solid(x, y)) {
+                return Some((x, y));
+            }
+        }
+        None
+    }
+
+    /// Clears the pixels within `radius` of the pixel, and returns how many were solid.
+    pub fn crater(&mut self, (x, y): (i32, i32), radius: f32) -> usize {
+        let reach = radius.ceil() as i32;
+        let mut cleared = 0;
+        for cy in y - reach..=y + reach {
+            for cx in x - reach..=x + reach {
+                let (dx, dy) = ((cx - x) as f32, (cy - y) as f32);
+                if dx * dx + dy * dy <= radius * radius {
+                    cleared += self.clear(cx, cy) as usize;
+                }
+            }
+        }
+        cleared
+    }
+
+    /// Clears the pixels within the columns `x0..=x1` and the rows `y0..=y1`, and returns how many were solid.
+    pub fn clear_rect(&mut self, (x0, y0): (i32, i32), (x1, y1): (i32, i32)) -> usize {
+        let mut cleared = 0;
+        for y in y0..=y1 {
+            for x in x0..=x1 {
+                cleared += self.clear(x, y) as usize;
+            }
+        }
+        cleared
+    }
+
+    /// RGBA8 pixels of the mask, `color` where solid and transparent elsewhere.
+    pub fn to_rgba(&self, color: [u8; 4]) -> Vec<u8> {
+        self.solid
+            .iter()
+            .flat_map(|&solid| if solid { color } else { [0; 4] })
+            .collect()
+    }
+
+    fn index(&self, x: i32, y: i32) -> Option<usize> {
+        let inside = (0..self.width as i32).contains(&x) && (0..self.height as i32).contains(&y);
+        inside.then(|| y as usize * self.width + x as usize)
+    }
+
+    /// Clears the pixel, and returns whether it was solid.
+    fn clear(&mut self, x: i32, y: i32) -> bool {
+        match self.index(x, y) {
+            Some(index) => std::mem::replace(&mut self.solid[index], false),
+            None => false,
+        }
+    }
+}
+
+// region: --- Components
+
+/// Component - Shield between the player and the invaders, eroded pixel by pixel
+#[derive(Component)]
+pub struct Bunker {
+    pub mask: BunkerMask,
+}
+
+impl Bunker {
+    /// The pixels of the mask a rectangle of the world (center and size) covers, top left and
+    /// bottom right, given the transform of the bunker.
+    fn pixels_under(
+        &self,
+        transform: &Transform,
+        center: Vec2,
+        size: Vec2,
+    ) -> ((i32, i32), (i32, i32)) {
+        let scale = transform.scale.xy();
+        let mask_size = Vec2::new(self.mask.width() as f32, self.mask.height() as f32);
+        let top_left =
+            transform.translation.xy() + Vec2::new(-mask_size.x, mask_size.y) * scale / 2.;
+
+        let pixel = |point: Vec2| {
+            let offset = (point - top_left) / scale;
+            (offset.x.floor() as i32, (-offset.y).floor() as i32)
+        };
+        (
+            pixel(center + Vec2::new(-size.x, size.y) / 2.),
+            pixel(center + Vec2::new(size.x, -size.y) / 2.),
+        )
+    }
+}
+
+// endregion: --- Components
+
+/// Bunkers of the classic mode, spawned with the game.
+/// Without a window there is no texture to redraw, the masks still take the damage.
+pub struct BunkerPlugin;
+
+impl Plugin for BunkerPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system_set(SystemSet::on_enter(AppState::InGame).with_system(bunker_setup_system))
+            .add_system_set(SystemSet::on_exit(AppState::InGame).with_system(bunker_cleanup_system))
+            .add_system_set_to_stage(
+                SimulationStage,
+                SystemSet::new()
+                    .with_run_criteria(in_game)
+                    .label(SimLabel::Collision)
+                    .after(SimLabel::Movement)
+                    .with_system(bunker_laser_hit_system)
+                    .with_system(bunker_invader_system),
+            );
+
+        if app.world.contains_resource::<Assets<Image>>() {
+            app.add_system_to_stage(CoreStage::PostUpdate, bunker_texture_system);
+        }
+    }
+}
+
+fn bunker_setup_system(
+    mut commands: Commands,
+    game_mode: Res<GameMode>,
+    win_size: Res<WinSize>,
+    mut images: Option<ResMut<Assets<Image>>>,
+) {
+    if *game_mode != GameMode::Classic {
+        return;
+    }
+
+    let mask = BunkerMask::classic();
+    let (width, height) = (mask.width() as f32, mask.height() as f32);
+    let y = -win_size.height / 2. + BUNKER_BOTTOM + height * BUNKER_PIXEL / 2.;
+
+    for i in 0..BUNKER_COUNT {
+        // evenly spread, as far from each other as from the edges
+        let x = win_size.width * (i as f32 + 1.) / (BUNKER_COUNT as f32 + 1.) - win_size.width / 2.;
+        let texture = images
+            .as_mut()
+            .map_or_else(Handle::default, |images| images.add(bunker_image(&mask)));
+
+        commands
+            .spawn_bundle(SpriteBundle {
+                texture,
+                transform: Transform {
+                    translation: Vec3::new(x, y, 5.),
+                    scale: Vec3::new(BUNKER_PIXEL, BUNKER_PIXEL, 1.),
+                    ..Default::default()
+                },
+                ..Default::default()
+            })
+            .insert(Bunker { mask: mask.clone() })
+            .insert(SpriteSize::from((width, height)));
+    }
+}
+
+/// Texture of the mask, with its pixels kept sharp when scaled up
+fn bunker_image(mask: &BunkerMask) -> Image {
+    let mut image = Image::new(
+        Extent3d {
+            width: mask.width() as u32,
+            height: mask.height() as u32,
+            depth_or_array_layers: 1,
+        },
+        TextureDimension::D2,
+        mask.to_rgba(BUNKER_COLOR),
+        TextureFormat::Rgba8UnormSrgb,
+    );
+    image.sampler_descriptor.mag_filter = FilterMode::Nearest;
+    image.sampler_descriptor.min_filter = FilterMode::Nearest;
+    image
+}
+
+fn bunker_cleanup_system(mut commands: Commands, query: Query<Entity, With<Bunker>>) {
+    for entity in query.iter() {
+        commands.entity(entity).despawn();
+    }
+}
+
+/// Stops the lasers, of the player and of the enemies alike, at the first solid pixel in their way,
+/// and blasts a crater around it.
+fn bunker_laser_hit_system(
+    mut commands: Commands,
+    laser_query: Query<(Entity, &Transform, &SpriteSize, &Velocity), With<Laser>>,
+    mut bunker_query: Query<(&Transform, &SpriteSize, &mut Bunker)>,
+) {
+    for (laser_entity, laser_tf, laser_size, velocity) in laser_query.iter() {
+        let laser_size = laser_size.0 * laser_tf.scale.xy();
+
+        for (bunker_tf, bunker_size, mut bunker) in bunker_query.iter_mut() {
+            let collision = collide(
+                laser_tf.translation,
+                laser_size,
+                bunker_tf.translation,
+                bunker_size.0 * bunker_tf.scale.xy(),
+            );
+            if collision.is_none() {
+                continue;
+            }
+
+            // the laser may only overlap the hole of the arch, or a crater
+            let (top_left, bottom_right) =
+                bunker.pixels_under(bunker_tf, laser_tf.translation.xy(), laser_size);
+            if let Some(hit) = bunker
+                .mask
+                .first_solid(top_left, bottom_right, velocity.y < 0.)
+            {
+                bunker.mask.crater(hit, CRATER_RADIUS);
+                commands.entity(laser_entity).despawn();
+                break;
+            }
+        }
+    }
+}
+
+/// Invaders marching over a bunker wipe out what they cover.
+fn bunker_invader_system(
+    invader_query: Query<(&Transform, &SpriteSize), With<Invader>>,
+    mut bunker_query: Query<(&Transform, &SpriteSize, &mut Bunker)>,
+) {
+    for (bunker_tf, bunker_size, mut bunker) in bunker_query.iter_mut() {
+        let bunker_size = bunker_size.0 * bunker_tf.scale.xy();
+
+        for (invader_tf, invader_size) in invader_query.iter() {
+            let invader_size = invader_size.0 * invader_tf.scale.xy();
+            if collide(
+                invader_tf.translation,
+                invader_size,
+                bunker_tf.translation,
+                bunker_size,
+            )
+            .is_none()
+            {
+                continue;
+            }
+
+            // only touch the mask when there is something to clear, so the texture is not redrawn for nothing
+            let (top_left, bottom_right) =
+                bunker.pixels_under(bunker_tf, invader_tf.translation.xy(), invader_size);
+            if bunker
+                .mask
+                .first_solid(top_left, bottom_right, true)
+                .is_some()
+            {
+                bunker.mask.clear_rect(top_left, bottom_right);
+            }
+        }
+    }
+}
+
+/// Redraws the texture of every bunker that took damage.
+fn bunker_texture_system(
+    mut images: ResMut<Assets<Image>>,
+    query: Query<(&Bunker, &Handle<Image>), Changed<Bunker>>,
+) {
+    for (bunker, handle) in query.iter() {
+        if let Some(image) = images.get_mut(handle) {
+            image.data = bunker.mask.to_rgba(BUNKER_COLOR);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn classic_cuts_the_corners_and_the_arch() {
+        let mask = BunkerMask::classic();
+        let (width, height) = (mask.width() as i32, mask.height() as i32);
+
+        // top corners
+        assert!(!mask.is_solid(0, 0));
+        assert!(!mask.is_solid(width - 1, 0));
+        assert!(!mask.is_solid(1, 2));
+        assert!(!mask.is_solid(width - 2, 2));
+        // bottom arch
+        assert!(!mask.is_solid(width / 2, height - 1));
+        assert!(!mask.is_solid(width / 2 - 1, height - 3));
+        // solid elsewhere: top middle, bottom feet, and above the arch
+        assert!(mask.is_solid(width / 2, 0));
+        assert!(mask.is_solid(0, height - 1));
+        assert!(mask.is_solid(width - 1, height - 1));
+        assert!(mask.is_solid(width / 2, height - 8));
+        assert!(mask.solid_count() < mask.width() * mask.height());
+    }
+
+    #[test]
+    fn crater_clears_within_the_radius() {
+        let mut mask = BunkerMask::new(10, 10);
+        let cleared = mask.crater((5, 5), 1.5);
+
+        // the center, its 4 neighbours and its 4 diagonals
+        assert_eq!(cleared, 9);
+        assert_eq!(mask.solid_count(), 100 - 9);
+        assert!(!mask.is_solid(4, 4));
+        assert!(!mask.is_solid(6, 6));
+        assert!(mask.is_solid(5, 3));
+        assert!(mask.is_solid(7, 5));
+
+        // nothing left to clear the second time
+        assert_eq!(mask.crater((5, 5), 1.5), 0);
+    }
+
+    #[test]
+    fn first_solid_goes_the_way_of_the_shot() {
+        let mut mask = BunkerMask::new(4, 6);
+        mask.clear_rect((0, 0), (3, 1));
+        mask.clear_rect((0, 4), (3, 5));
+
+        assert_eq!(mask.first_solid((0, 0), (3, 5), true), Some((0, 2)));
+        assert_eq!(mask.first_solid((0, 0), (3, 5), false), Some((0, 3)));
+        assert_eq!(mask.first_solid((2, 0), (3, 1), true), None);
+        // the area is clipped to the mask
+        assert_eq!(mask.first_solid((-5, -5), (10, 10), true), Some((0, 2)));
+    }
+
+    #[test]
+    fn clearing_outside_the_mask_does_nothing() {
+        let mut mask = BunkerMask::new(4, 4);
+
+        assert_eq!(mask.clear_rect((-3, -3), (-1, -1)), 0);
+        assert_eq!(mask.clear_rect((4, 0), (6, 3)), 0);
+        assert_eq!(mask.crater((-10, 20), 2.5), 0);
+        assert_eq!(mask.solid_count(), 16);
+        assert!(!mask.is_solid(-1, 0));
+        assert!(!mask.is_solid(0, 4));
+    }
+
+    #[test]
+    fn pixels_under_maps_the_world_to_the_mask() {
+        let bunker = Bunker {
+            mask: BunkerMask::new(20, 10),
+        };
+        // 3 screen pixels per mask pixel, centered on (100, 50): the mask spans x 70..130 and y 35..65
+        let transform = Transform {
+            translation: Vec3::new(100., 50., 0.),
+            scale: Vec3::new(3., 3., 1.),
+            ..Default::default()
+        };
+
+        // the top left pixel, and the bottom right one
+        assert_eq!(
+            bunker.pixels_under(&transform, Vec2::new(71.5, 63.5), Vec2::ONE),
+            ((0, 0), (0, 0))
+        );
+        assert_eq!(
+            bunker.pixels_under(&transform, Vec2::new(128.5, 36.5), Vec2::ONE),
+            ((19, 9), (19, 9))
+        );
+        // a rectangle across the center
+        assert_eq!(
+            bunker.pixels_under(&transform, Vec2::new(100., 50.), Vec2::new(6., 6.)),
+            ((9, 4), (11, 6))
+        );
+    }
+}
@@ -1,6 +1,10 @@
 use std::iter::from_fn;
 
-use bevy::{prelude::Component, math::{Vec2, Vec3}, core::Timer};
+use bevy::{
+    core::Timer,
+    math::{Vec2, Vec3},
+    prelude::Component,
+};
 
 // region: --- Common Components
 
@@ -14,21 +18,60 @@ pub struct Velocity {
 #[derive(Component)]
 pub struct Attributes {
     pub health: f32,
+    pub max_health: f32,
 }
 
 impl Default for Attributes {
     fn default() -> Self {
         Attributes {
             health: 100.0,
+            max_health: 100.0,
         }
     }
 }
 
+impl Attributes {
+    /// Takes the damage, less what the armor (if any) stops, and returns the damage taken.
+    pub fn take_damage(&mut self, damage: &Damage, armor: Option<&Armor>) -> f32 {
+        let taken = armor.map_or(damage.0, |armor| armor.reduce(damage.0));
+        self.health -= taken;
+        taken
+    }
+
+    pub fn is_dead(&self) -> bool {
+        self.health <= 0.
+    }
+}
+
+/// Damage dealt by a laser (or anything else) on impact
+#[derive(Component, Clone, Copy)]
+pub struct Damage(pub f32);
+
+/// Cuts the damage an entity takes, first by `resist` (a fraction, 0 to 1), then by `flat` points.
+#[derive(Component, Clone, Copy, Default)]
+pub struct Armor {
+    pub resist: f32,
+    pub flat: f32,
+}
+
+impl Armor {
+    pub fn reduce(&self, damage: f32) -> f32 {
+        (damage * (1. - self.resist) - self.flat).max(0.)
+    }
+}
+
 #[derive(Component)]
 pub struct Movable {
     pub auto_despawn: bool,
 }
 
+/// Position at the start and at the end of the last simulation tick, rendering lerps between the two.
+#[derive(Component)]
+pub struct Interpolated {
+    pub previous: Vec2,
+    pub current: Vec2,
+}
+
 #[derive(Component)]
 pub struct Laser;
 
@@ -51,6 +94,18 @@ pub struct HealthText;
 #[derive(Component)]
 pub struct ScoreText;
 
+#[derive(Component)]
+pub struct LivesText;
+
+#[derive(Component)]
+pub struct WaveText;
+
+#[derive(Component)]
+pub struct WeaponText;
+
+#[derive(Component)]
+pub struct PowerUpText;
+
 // endregion: --- UI Components
 
 // region: --- Player Components
@@ -78,8 +133,9 @@ pub struct FromEnemy;
 #[derive(Component)]
 pub struct Explosion;
 
+/// Explosion to spawn at the translation, with the scale
 #[derive(Component)]
-pub struct ExplosionToSpawn(pub Vec3);
+pub struct ExplosionToSpawn(pub Vec3, pub f32);
 
 #[derive(Component)]
 pub struct ExplosionTimer(pub Timer);
@@ -0,0 +1,126 @@
+use std::{env, fmt::Debug, ops::RangeBounds, path::PathBuf};
+
+use crate::{GameMode, PLAYER_MOVE_BAND, TIME_STEP, WINDOW_HEIGHT, WINDOW_WIDTH};
+
+/// Resource - Game Config (parsed from the command line)
+#[derive(Clone, Debug)]
+pub struct GameConfig {
+    pub headless: bool,
+    pub ticks: u64, // only used in headless mode
+    pub width: f32,
+    pub height: f32,
+    pub seed: u64,
+    pub record: Option<PathBuf>, // replay file to write
+    pub replay: Option<PathBuf>, // replay file to play back
+    pub dead_zone: f32,          // gamepad stick dead zone, 0 to 1
+    pub move_band: f32, // fraction of the window height, from the bottom, the player can move in
+    pub level: Option<String>, // scripted level, relative to the assets folder
+    pub mode: GameMode, // the menu can change it, before the game starts
+}
+
+impl Default for GameConfig {
+    fn default() -> Self {
+        Self {
+            headless: false,
+            ticks: (120. / TIME_STEP) as u64, // two minutes of game time
+            width: WINDOW_WIDTH,
+            height: WINDOW_HEIGHT,
+            seed: rand::random(), // a fresh match unless `--seed` is given
+            record: None,
+            replay: None,
+            dead_zone: 0.25,
+            move_band: PLAYER_MOVE_BAND,
+            level: None,
+            mode: GameMode::Waves,
+        }
+    }
+}
+
+impl GameConfig {
+    /// Parses the process arguments, e.g. `invaders --headless --ticks 3600 --seed 42`.
+    pub fn from_args() -> Result<Self, String> {
+        Self::parse(env::args().skip(1))
+    }
+
+    pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Self, String> {
+        let mut config = Self::default();
+        let mut args = args.into_iter();
+
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--headless" => config.headless = true,
+                "--ticks" => config.ticks = parse_value(&arg, args.next())?,
+                "--width" => config.width = parse_value(&arg, args.next())?,
+                "--height" => config.height = parse_value(&arg, args.next())?,
+                "--seed" => config.seed = parse_value(&arg, args.next())?,
+                "--record" => config.record = Some(parse_value(&arg, args.next())?),
+                "--replay" => config.replay = Some(parse_value(&arg, args.next())?),
+                "--dead-zone" => config.dead_zone = parse_in(&arg, args.next(), 0.0..1.0)?,
+                "--move-band" => config.move_band = parse_in(&arg, args.next(), 0.0..=1.0)?,
+                "--level" => config.level = Some(parse_value(&arg, args.next())?),
+                "--mode" => config.mode = parse_value(&arg, args.next())?,
+                _ => return Err(format!("unknown argument `{}`", arg)),
+            }
+        }
+
+        Ok(config)
+    }
+}
+
+fn parse_value<T: std::str::FromStr>(name: &str, value: Option<String>) -> Result<T, String> {
+    let value = value.ok_or_else(|| format!("missing value for `{}`", name))?;
+    value
+        .parse()
+        .map_err(|_| format!("invalid value `{}` for `{}`", value, name))
+}
+
+fn parse_in<T, R>(name: &str, value: Option<String>, range: R) -> Result<T, String>
+where
+    T: std::str::FromStr + PartialOrd,
+    R: RangeBounds<T> + Debug,
+{
+    let parsed = parse_value(name, value.clone())?;
+    if range.contains(&parsed) {
+        Ok(parsed)
+    } else {
+        Err(format!(
+            "invalid value `{}` for `{}`, expected {:?}",
+            value.unwrap_or_default(),
+            name,
+            range
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<GameConfig, String> {
+        GameConfig::parse(args.iter().map(|arg| arg.to_string()))
+    }
+
+    #[test]
+    fn move_band_is_a_fraction() {
+        assert_eq!(parse(&["--move-band", "0.5"]).unwrap().move_band, 0.5);
+        assert_eq!(parse(&["--move-band", "1"]).unwrap().move_band, 1.);
+        assert_eq!(parse(&["--move-band", "0"]).unwrap().move_band, 0.);
+        assert_eq!(
+            parse(&["--move-band", "1.5"]).unwrap_err(),
+            "invalid value `1.5` for `--move-band`, expected 0.0..=1.0"
+        );
+        assert!(parse(&["--move-band", "-0.1"]).is_err());
+        assert!(parse(&["--move-band", "half"]).is_err());
+    }
+
+    #[test]
+    fn dead_zone_is_below_one() {
+        assert_eq!(parse(&["--dead-zone", "0"]).unwrap().dead_zone, 0.);
+        assert_eq!(parse(&["--dead-zone", "0.9"]).unwrap().dead_zone, 0.9);
+        assert_eq!(
+            parse(&["--dead-zone", "1"]).unwrap_err(),
+            "invalid value `1` for `--dead-zone`, expected 0.0..1.0"
+        );
+        assert!(parse(&["--dead-zone", "-0.5"]).is_err());
+    }
+}
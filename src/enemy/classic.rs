@@ -0,0 +1,331 @@
+use bevy::prelude::*;
+use rand::Rng;
+
+use super::{spawn_enemy_laser, EnemyKind, Wave, WavePhase};
+use crate::{
+    components::{Attributes, Enemy, ExplosionToSpawn, FromEnemy, Laser, Player, SpriteSize},
+    EnemyCount, GameMode, GameRng, GameSounds, GameTextures, PlayerState, SimTime, WinSize,
+    PLAYER_SIZE, SPRITE_SCALE, TIME_STEP,
+};
+use bevy_kira_audio::Audio;
+
+pub const PAR_TIME: f64 = 90.; // seconds to clear a grid in for a time bonus
+const GRID_COLUMNS: u32 = 11;
+/// Invader of every row, from the top
+const GRID_ROWS: [EnemyKind; 5] = [
+    EnemyKind::Squid,
+    EnemyKind::Crab,
+    EnemyKind::Crab,
+    EnemyKind::Octopus,
+    EnemyKind::Octopus,
+];
+const GRID_SPACING: (f32, f32) = (42., 34.);
+const GRID_TOP: f32 = 110.; // from the top of the window to the first row, below the HUD
+const MARCH_STEP: f32 = 8.; // sideways, every step
+const MARCH_DROP: f32 = 20.; // down, at the edges of the window, and for every wave cleared
+const MARCH_WAVE_DROPS: u32 = 4; // the most the grid of a later wave starts lower by
+const MARCH_INTERVAL: f64 = 0.6; // seconds between the steps of the full grid, the fewer invaders the faster
+const MARCH_MARGIN: f32 = 10.; // from the edges of the window
+const MARCH_ROOM: f32 = 4. * MARCH_STEP; // sideways room the grid keeps to march in, narrow windows squeeze it
+const INVADER_LASERS_MAX: usize = 3; // invader lasers on screen at once
+const RELOAD_INTERVAL: f64 = 1.2; // seconds between two invader lasers, less in later waves
+const RELOAD_JITTER: f64 = 0.5; // fraction of the interval a laser may come sooner or later by
+
+// region: --- Components
+
+/// Component - Invader of the classic grid, moved by the march instead of a `Formation`
+#[derive(Component)]
+pub struct Invader;
+
+// endregion: --- Components
+
+// region: --- Resources
+
+/// Resource - March of the classic grid
+pub struct ClassicMarch {
+    dir: f32,       // 1 to the right, -1 to the left
+    next_step: f64, // sim time
+    total: u32,     // invaders the grid started with
+    next_shot: f64, // sim time
+}
+
+impl Default for ClassicMarch {
+    fn default() -> Self {
+        Self {
+            dir: 1.,
+            next_step: 0.,
+            total: GRID_COLUMNS * GRID_ROWS.len() as u32,
+            next_shot: 0.,
+        }
+    }
+}
+
+// endregion: --- Resources
+
+/// Spawns the whole grid at once when a wave of the classic mode starts, a row lower every wave.
+pub fn classic_spawn_system(
+    mut commands: Commands,
+    game_mode: Res<GameMode>,
+    game_textures: Res<GameTextures>,
+    mut enemy_count: ResMut<EnemyCount>,
+    mut march: ResMut<ClassicMarch>,
+    mut wave: ResMut<Wave>,
+    sim_time: Res<SimTime>,
+    win_size: Res<WinSize>,
+) {
+    let due =
+        matches!(wave.phase, WavePhase::Spawning { next_spawn } if sim_time.elapsed >= next_spawn);
+    if *game_mode != GameMode::Classic || !due {
+        return;
+    }
+
+    let drops = (wave.number - 1).min(MARCH_WAVE_DROPS);
+    let top = win_size.height / 2. - GRID_TOP - drops as f32 * MARCH_DROP;
+    let spacing = grid_spacing(win_size.width);
+    let left = -((GRID_COLUMNS - 1) as f32) * spacing / 2.;
+
+    for (row, kind) in GRID_ROWS.into_iter().enumerate() {
+        let enemy_type = kind.def();
+        for column in 0..GRID_COLUMNS {
+            let x = left + column as f32 * spacing;
+            let y = top - row as f32 * GRID_SPACING.1;
+
+            commands
+                .spawn_bundle(SpriteBundle {
+                    texture: game_textures
+                        .enemies
+                        .get(&kind)
+                        .cloned()
+                        .unwrap_or_default(),
+                    sprite: Sprite {
+                        color: enemy_type.color,
+                        ..Default::default()
+                    },
+                    transform: Transform {
+                        translation: Vec3::new(x, y, 10.),
+                        scale: Vec3::new(enemy_type.scale, enemy_type.scale, 1.),
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                })
+                .insert(Enemy)
+                .insert(Invader)
+                .insert(kind)
+                .insert(SpriteSize::from(enemy_type.size))
+                .insert(Attributes {
+                    health: enemy_type.health,
+                    max_health: enemy_type.health,
+                });
+        }
+    }
+
+    *march = ClassicMarch {
+        next_step: sim_time.elapsed + MARCH_INTERVAL,
+        next_shot: sim_time.elapsed + RELOAD_INTERVAL,
+        ..Default::default()
+    };
+    enemy_count.0 += march.total;
+    wave.spawned(&sim_time, None);
+}
+
+/// Columns of the grid as far apart as they can be while the 11 of them still fit in a window
+/// `width` wide, with room to march.
+fn grid_spacing(width: f32) -> f32 {
+    let widest = GRID_ROWS
+        .iter()
+        .map(|kind| kind.def().size.0 * kind.def().scale)
+        .fold(0., f32::max);
+    let room = width - 2. * MARCH_MARGIN - MARCH_ROOM - widest;
+    GRID_SPACING.0.min(room / (GRID_COLUMNS - 1) as f32).max(0.)
+}
+
+/// Steps the grid sideways in lockstep, and a row down (turning around) when it reaches an edge.
+/// The fewer invaders are left, the faster it goes.
+pub fn classic_march_system(
+    mut march: ResMut<ClassicMarch>,
+    wave: Res<Wave>,
+    sim_time: Res<SimTime>,
+    win_size: Res<WinSize>,
+    mut query: Query<(&mut Transform, &SpriteSize), With<Invader>>,
+) {
+    let alive = query.iter().count() as u32;
+    if alive == 0 || sim_time.elapsed < march.next_step {
+        return;
+    }
+
+    // would the next step take any invader past the edge it marches towards?
+    let edge = win_size.width / 2. - MARCH_MARGIN;
+    let dir = march.dir;
+    let at_edge = query.iter().any(|(transform, size)| {
+        let half_width = size.0.x * transform.scale.x / 2.;
+        dir * (transform.translation.x + dir * MARCH_STEP) + half_width > edge
+    });
+
+    let step = if at_edge {
+        march.dir = -dir;
+        Vec3::new(0., -MARCH_DROP, 0.)
+    } else {
+        Vec3::new(dir * MARCH_STEP, 0., 0.)
+    };
+    for (mut transform, _) in query.iter_mut() {
+        transform.translation += step;
+    }
+
+    let interval = MARCH_INTERVAL * alive as f64 / march.total as f64 / wave.difficulty() as f64;
+    march.next_step = sim_time.elapsed + interval.max(TIME_STEP as f64);
+}
+
+/// Fires from the bottom invader of a random column once the grid has reloaded, with only a few
+/// invader lasers on screen at once. The grid reloads faster in later waves.
+pub fn classic_fire_system(
+    mut commands: Commands,
+    game_textures: Res<GameTextures>,
+    game_sounds: Res<GameSounds>,
+    mut march: ResMut<ClassicMarch>,
+    mut rng: ResMut<GameRng>,
+    wave: Res<Wave>,
+    sim_time: Res<SimTime>,
+    invader_query: Query<(&Transform, &EnemyKind), With<Invader>>,
+    laser_query: Query<(), (With<Laser>, With<FromEnemy>)>,
+    audio: Res<Audio>,
+) {
+    // a grid with a laser due waits for one of its lasers to leave the screen
+    if sim_time.elapsed < march.next_shot || laser_query.iter().count() >= INVADER_LASERS_MAX {
+        return;
+    }
+
+    // the bottom invader of every column, in a fixed order so the roll picks the same one every run
+    let mut bottoms: Vec<(&Transform, &EnemyKind)> = Vec::new();
+    for (transform, kind) in invader_query.iter() {
+        let x = transform.translation.x;
+        match bottoms
+            .iter_mut()
+            .find(|(other, _)| (other.translation.x - x).abs() < 1.)
+        {
+            Some(bottom) if transform.translation.y < bottom.0.translation.y => {
+                *bottom = (transform, kind)
+            }
+            Some(_) => {}
+            None => bottoms.push((transform, kind)),
+        }
+    }
+    if bottoms.is_empty() {
+        return;
+    }
+    bottoms.sort_by(|(a, _), (b, _)| a.translation.x.total_cmp(&b.translation.x));
+
+    let (transform, kind) = bottoms[rng.0.gen_range(0..bottoms.len())];
+    let (x, y) = (transform.translation.x, transform.translation.y);
+    spawn_enemy_laser(&mut commands, &game_textures, x, y, kind.def().laser_damage);
+    audio.play(game_sounds.enemy_laser.clone());
+
+    let interval = RELOAD_INTERVAL * (1. + rng.0.gen_range(-RELOAD_JITTER..=RELOAD_JITTER));
+    march.next_shot = sim_time.elapsed + interval / wave.difficulty() as f64;
+}
+
+/// Ends the game when the grid reaches the player's row, whatever lives are left.
+pub fn classic_invasion_system(
+    mut commands: Commands,
+    mut player_state: ResMut<PlayerState>,
+    sim_time: Res<SimTime>,
+    win_size: Res<WinSize>,
+    invader_query: Query<(&Transform, &SpriteSize), With<Invader>>,
+    player_query: Query<(Entity, &Transform), With<Player>>,
+    game_sounds: Res<GameSounds>,
+    audio: Res<Audio>,
+) {
+    if player_state.lives == 0 {
+        return;
+    }
+
+    let player_row_top = -win_size.height / 2. + PLAYER_SIZE.1 * SPRITE_SCALE + 5.;
+    let landed = invader_query.iter().any(|(transform, size)| {
+        transform.translation.y - size.0.y * transform.scale.y / 2. <= player_row_top
+    });
+    if !landed {
+        return;
+    }
+
+    if let Ok((player_entity, player_tf)) = player_query.get_single() {
+        commands.entity(player_entity).despawn();
+        commands
+            .spawn()
+            .insert(ExplosionToSpawn(player_tf.translation, 1.));
+        audio.play(game_sounds.player_explosion.clone());
+        player_state.shot(sim_time.elapsed);
+    }
+    // no respawn, the game is over once the explosion played out
+    player_state.lives = 0;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Spawns the grid in a window `width` wide, marches it for `steps` steps, and returns the
+    /// sideways steps and drops it took.
+    fn march_in(width: f32, steps: u32) -> (u32, u32) {
+        let mut world = World::new();
+        world.insert_resource(GameMode::Classic);
+        world.insert_resource(GameTextures::default());
+        world.insert_resource(EnemyCount(0));
+        world.insert_resource(ClassicMarch::default());
+        world.insert_resource(Wave {
+            phase: WavePhase::Spawning { next_spawn: 0. },
+            ..Default::default()
+        });
+        world.insert_resource(SimTime::default());
+        world.insert_resource(WinSize {
+            width,
+            height: 676.,
+        });
+
+        let mut spawn = SystemStage::single_threaded().with_system(classic_spawn_system);
+        spawn.run(&mut world);
+        let mut march = SystemStage::single_threaded().with_system(classic_march_system);
+
+        let edge = width / 2. - MARCH_MARGIN;
+        let (mut sideways, mut drops) = (0, 0);
+        let mut last = world
+            .query::<&Transform>()
+            .iter(&world)
+            .next()
+            .unwrap()
+            .translation;
+        while sideways + drops < steps {
+            world.resource_mut::<SimTime>().advance();
+            march.run(&mut world);
+
+            let mut query = world.query_filtered::<(&Transform, &SpriteSize), With<Invader>>();
+            for (transform, size) in query.iter(&world) {
+                let half_width = size.0.x * transform.scale.x / 2.;
+                assert!(
+                    transform.translation.x.abs() + half_width <= edge,
+                    "an invader left the window"
+                );
+            }
+            let now = query.iter(&world).next().unwrap().0.translation;
+            if now.y < last.y {
+                drops += 1;
+            } else if now.x != last.x {
+                sideways += 1;
+            }
+            last = now;
+        }
+        (sideways, drops)
+    }
+
+    #[test]
+    fn the_grid_marches_across_narrow_windows() {
+        for width in [598., 400., 300.] {
+            let (sideways, drops) = march_in(width, 60);
+            assert!(
+                sideways >= 4 * drops,
+                "in {} pixels the grid stepped {} and dropped {}",
+                width,
+                sideways,
+                drops
+            );
+        }
+    }
+}
@@ -1,16 +1,329 @@
-use bevy::prelude::Component;
+use std::{f32::consts::PI, ops::Range};
+
+use bevy::prelude::{Component, Vec2};
 use rand::Rng;
 
-use crate::{WinSize, FORMATION_MEMBERS_MAX, BASE_SPEED};
+use crate::{SimTime, WinSize, BASE_SPEED, TIME_STEP};
+
+pub const ENTRY_MARGIN: f32 = 100.; // how far off screen the formations enter from, and dives leave to
+pub const GRID_FLOOR: f32 = -0.1; // fraction of the window height from its center, the march stops stepping down at
+const GRID_MARCH_SPEED: f32 = 0.1; // fraction of the formation speed the grid marches at
+const SWEEP_SPEED: f32 = 0.5; // fraction of the formation speed the sine sweeps at
+const HOLD_SPACING: f32 = 45.; // between the members holding before a dive or swoop
+
+/// Kinds of path a wave can pick from, the maker rolls their parameters
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PathKind {
+    Orbit,
+    GridMarch,
+    SineSweep,
+    FigureEight,
+    Spline,
+    Dive,
+    Swoop,
+}
+
+/// Path a formation flies once it entered, in pixels from the center of the window
+#[derive(Clone, Debug, PartialEq)]
+pub enum FormationPath {
+    /// circles the ellipse of `radius` around `pivot`
+    Orbit { pivot: Vec2, radius: Vec2 },
+    /// the classic march, all members in lockstep: rows of `columns` members `spacing` apart
+    /// from `origin`, across `width` and a `step` down at every edge, but not below `floor`
+    GridMarch {
+        origin: Vec2,
+        columns: u32,
+        spacing: f32,
+        width: f32,
+        step: f32,
+        floor: f32,
+    },
+    /// sweeps from side to side across `width`, waving `amplitude` up and down around `y`
+    SineSweep {
+        y: f32,
+        width: f32,
+        amplitude: f32,
+        wavelength: f32,
+    },
+    /// figure eight of `radius` around `center`
+    FigureEight { center: Vec2, radius: Vec2 },
+    /// loops through the `points`, a closed Catmull-Rom spline
+    Spline { points: Vec<Vec2> },
+    /// holds at `hold_at` for `hold` seconds, dives straight at the player, and comes back in from the top
+    Dive { hold_at: Vec2, hold: f32 },
+    /// holds at `hold_at` for `hold` seconds, then swoops `depth` down and back up, a Bézier loop `width` wide
+    Swoop {
+        hold_at: Vec2,
+        hold: f32,
+        depth: f32,
+        width: f32,
+    },
+}
 
 /// Component - Enemy Formation (per enemy)
 #[derive(Clone, Component)]
 pub struct Formation {
     pub start: (f32, f32),
-    pub radius: (f32, f32),
     pub speed: f32,
-    pub pivot: (f32, f32),
-    pub angle: f32, // change per tick
+    pub path: FormationPath,
+    pub member: u32,       // index in the formation
+    pub started_at: f64,   // sim time the formation was made, the clock of the grid march
+    pub t: f32, // progress on the path: the angle of the orbit and figure eight, seconds otherwise
+    pub aim: Option<Vec2>, // direction of the dive in progress
+}
+
+impl Formation {
+    /// Formation entering at `start` and then flying the `path`.
+    pub fn new(start: (f32, f32), path: FormationPath, started_at: f64) -> Self {
+        let t = match &path {
+            // start angle, from the pivot toward the entry point
+            FormationPath::Orbit { pivot, .. } => (start.1 - pivot.y).atan2(start.0 - pivot.x),
+            _ => 0.,
+        };
+
+        Formation {
+            start,
+            // speed (fixed for now)
+            speed: BASE_SPEED,
+            path,
+            member: 0,
+            started_at,
+            t,
+            aim: None,
+        }
+    }
+
+    /// 1 for counter clockwise (or left to right), -1 for clockwise (or right to left)
+    fn dir(&self) -> f32 {
+        if self.start.0 < 0. {
+            1.
+        } else {
+            -1.
+        }
+    }
+
+    /// Moves the enemy at `position` one tick toward its path, and along it once it is on it.
+    /// Returns the new position, and whether it jumped there (a dive coming back in from the top).
+    pub fn step(
+        &mut self,
+        position: Vec2,
+        sim_time: &SimTime,
+        player: Option<Vec2>,
+        win_size: &WinSize,
+    ) -> (Vec2, bool) {
+        // a dive ends off screen, and starts over from the top
+        if self.aim.is_some() {
+            let (x_span, y_span) = (
+                win_size.width / 2. + ENTRY_MARGIN,
+                win_size.height / 2. + ENTRY_MARGIN,
+            );
+            if position.y < -y_span || position.x.abs() > x_span {
+                self.t = 0.;
+                self.aim = None;
+                return (Vec2::new(self.hold_at().x, y_span), true);
+            }
+        }
+
+        // aim once, where the player is when the dive starts
+        if let FormationPath::Dive { hold, .. } = self.path {
+            if self.aim.is_none() && self.t + TIME_STEP >= hold {
+                let hold_at = self.hold_at();
+                self.aim = player
+                    .map(|player| (player - hold_at).normalize_or_zero())
+                    .filter(|aim| *aim != Vec2::ZERO)
+                    .or(Some(-Vec2::Y));
+            }
+        }
+
+        // current position
+        let (x_org, y_org) = (position.x, position.y);
+
+        // max distance
+        let max_distance = TIME_STEP * self.speed;
+
+        // compute target x/y
+        let (t, dst) = self.next_target(position, sim_time);
+        let (x_dst, y_dst) = (dst.x, dst.y);
+
+        // compute distance
+        let dx = x_org - x_dst;
+        let dy = y_org - y_dst;
+        let distance = (dx * dx + dy * dy).sqrt();
+        let distance_ratio = if distance != 0. {
+            max_distance / distance
+        } else {
+            0.
+        };
+
+        // compute final x/y
+        let x = x_org - dx * distance_ratio;
+        let x = if dx > 0. { x.max(x_dst) } else { x.min(x_dst) };
+        let y = y_org - dy * distance_ratio;
+        let y = if dy > 0. { y.max(y_dst) } else { y.min(y_dst) };
+
+        // start moving along the path only when sprite is on or close to it
+        if distance < max_distance * self.speed / 20. {
+            self.t = t;
+        }
+
+        (Vec2::new(x, y), false)
+    }
+
+    /// Where the enemy heads for this tick, and the progress on the path once it gets there.
+    fn next_target(&self, position: Vec2, sim_time: &SimTime) -> (f32, Vec2) {
+        let dir = self.dir();
+        let hold_at = self.hold_at();
+
+        match &self.path {
+            FormationPath::Orbit { pivot, radius } => {
+                // compute next angle (based on time for now)
+                let angle =
+                    self.t + dir * self.speed * TIME_STEP / (radius.x.min(radius.y) * PI / 2.);
+                let dst = Vec2::new(
+                    radius.x * angle.cos() + pivot.x,
+                    radius.y * angle.sin() + pivot.y,
+                );
+                (angle, dst)
+            }
+            FormationPath::GridMarch {
+                origin,
+                columns,
+                spacing,
+                width,
+                step,
+                floor,
+            } => {
+                // every member follows the same clock, so the rows stay lined up
+                let clock = (sim_time.elapsed - self.started_at) as f32;
+                let travel = clock * self.speed * GRID_MARCH_SPEED;
+                let legs = (travel / width).floor();
+                let row_y = (origin.y - legs * step).max(*floor);
+
+                let columns = (*columns).max(1);
+                let slot = Vec2::new(
+                    dir * (self.member % columns) as f32 * spacing,
+                    -((self.member / columns) as f32) * spacing,
+                );
+                let dst = Vec2::new(origin.x + dir * bounce(travel, *width), row_y) + slot;
+                (self.t, dst)
+            }
+            FormationPath::SineSweep {
+                y,
+                width,
+                amplitude,
+                wavelength,
+            } => {
+                let t = self.t + TIME_STEP;
+                let travel = t * self.speed * SWEEP_SPEED;
+                let x = dir * (bounce(travel, *width) - width / 2.);
+                let y = y + amplitude * (2. * PI * travel / wavelength).sin();
+                (t, Vec2::new(x, y))
+            }
+            FormationPath::FigureEight { center, radius } => {
+                let angle =
+                    self.t + dir * self.speed * TIME_STEP / (radius.x.min(radius.y) * PI / 2.);
+                let dst =
+                    *center + Vec2::new(radius.x * angle.sin(), radius.y * (2. * angle).sin());
+                (angle, dst)
+            }
+            FormationPath::Spline { points } => {
+                let count = points.len();
+                if count < 2 {
+                    let dst = points.first().copied().unwrap_or(position);
+                    return (self.t, dst);
+                }
+
+                // as fast along the spline as along the line between its points
+                let index = self.t as usize % count;
+                let length = points[index].distance(points[(index + 1) % count]).max(1.);
+                let t = (self.t + self.speed * TIME_STEP / length) % count as f32;
+
+                let index = t as usize;
+                let point = |offset: usize| points[(index + count + offset - 1) % count];
+                let dst = catmull_rom([point(0), point(1), point(2), point(3)], t.fract());
+                (t, dst)
+            }
+            FormationPath::Dive { hold, .. } => {
+                let t = self.t + TIME_STEP;
+                if t < *hold {
+                    return (t, hold_at);
+                }
+
+                let aim = self.aim.unwrap_or(-Vec2::Y);
+                (t, hold_at + aim * (t - hold) * self.speed)
+            }
+            FormationPath::Swoop {
+                hold, depth, width, ..
+            } => {
+                let t = self.t + TIME_STEP;
+                if t < *hold {
+                    return (t, hold_at);
+                }
+
+                // a cubic Bézier from the hold point and back, reaching `depth` at its bottom
+                let down = -depth * 4. / 3.;
+                let points = [
+                    hold_at,
+                    hold_at + Vec2::new(-dir * width, down),
+                    hold_at + Vec2::new(dir * width, down),
+                    hold_at,
+                ];
+                let length: f32 = points
+                    .windows(2)
+                    .map(|pair| pair[0].distance(pair[1]))
+                    .sum();
+                let u = (t - hold) * self.speed / length;
+                if u >= 1. {
+                    // back at the hold point, hold again
+                    return (0., hold_at);
+                }
+                (t, bezier(points, u))
+            }
+        }
+    }
+
+    /// Where the member waits before a dive or swoop, side by side with the others.
+    fn hold_at(&self) -> Vec2 {
+        match &self.path {
+            FormationPath::Dive { hold_at, .. } | FormationPath::Swoop { hold_at, .. } => {
+                *hold_at - Vec2::new(self.dir() * self.member as f32 * HOLD_SPACING, 0.)
+            }
+            _ => Vec2::ZERO,
+        }
+    }
+}
+
+/// `low..high`, kept from going empty when the window is too small for `high`.
+fn span(low: f32, high: f32) -> Range<f32> {
+    low..high.max(low + 1.)
+}
+
+/// Goes from 0 to `width` and back as `travel` grows.
+fn bounce(travel: f32, width: f32) -> f32 {
+    let legs = (travel / width).floor();
+    let along = travel - legs * width;
+    if (legs as u32).is_multiple_of(2) {
+        along
+    } else {
+        width - along
+    }
+}
+
+/// Point `u` (0 to 1) of the Catmull-Rom segment between `points[1]` and `points[2]`.
+fn catmull_rom(points: [Vec2; 4], u: f32) -> Vec2 {
+    let [p0, p1, p2, p3] = points;
+    let (u2, u3) = (u * u, u * u * u);
+    0.5 * (2. * p1
+        + (p2 - p0) * u
+        + (2. * p0 - 5. * p1 + 4. * p2 - p3) * u2
+        + (3. * p1 - p0 - 3. * p2 + p3) * u3)
+}
+
+/// Point `u` (0 to 1) of the cubic Bézier curve.
+fn bezier(points: [Vec2; 4], u: f32) -> Vec2 {
+    let [p0, p1, p2, p3] = points;
+    let v = 1. - u;
+    v * v * v * p0 + 3. * v * v * u * p1 + 3. * v * u * u * p2 + u * u * u * p3
 }
 
 /// Resource - Formation Maker
@@ -22,46 +335,20 @@ pub struct FormationMaker {
 
 /// Formation factory implementation
 impl FormationMaker {
-    pub fn make(&mut self, win_size: &WinSize) -> Formation {
-        match (&self.current_template, self.current_members >= FORMATION_MEMBERS_MAX) {
+    /// Returns the formation of the next enemy: the current one, or the one `new` makes once
+    /// `members_max` enemies joined the current one.
+    pub fn join(&mut self, members_max: u32, new: impl FnOnce() -> Formation) -> Formation {
+        match (&self.current_template, self.current_members >= members_max) {
             // if has current template and still within max members
             (Some(tmpl), false) => {
+                let mut formation = tmpl.clone();
+                formation.member = self.current_members;
                 self.current_members += 1;
-                tmpl.clone()
+                formation
             }
             // if first formation or previous formation is full (need to create a new one)
             (None, _) | (_, true) => {
-                let mut rng = rand::thread_rng();
-
-                // compute the start x/y
-                let w_span = win_size.width as f32 / 2.0 + 100.0;
-                let h_span = win_size.height as f32 / 2.0 + 100.0;
-                let start_x = if rng.gen_bool(0.5) { w_span } else { -w_span };
-                let start_y = rng.gen_range(-h_span..h_span) as f32;
-                let start = (start_x, start_y);
-
-                // compute the pivot x/y
-                let w_span = win_size.width as f32 / 4.;
-                let h_span = win_size.height as f32 / 3. + 50.;
-                let pivot = (rng.gen_range(-w_span..w_span), rng.gen_range(0.0..h_span));
-
-                // compute the radious
-                let radius = (rng.gen_range(80.0..150.0), 100.);
-
-                // compute the start angle
-                let angle = (start_y - pivot.1).atan2(start_x - pivot.0);
-
-                // speed (fixed for now)
-                let speed = BASE_SPEED;
-
-                // create the formation
-                let formation = Formation {
-                    start,
-                    radius,
-                    pivot,
-                    angle,
-                    speed,
-                };
+                let formation = new();
 
                 // set the current template
                 self.current_template = Some(formation.clone());
@@ -72,4 +359,157 @@ impl FormationMaker {
             }
         }
     }
-}
\ No newline at end of file
+
+    /// Returns the formation of the next enemy of a built-in wave, flying one of the `paths`.
+    pub fn make(
+        &mut self,
+        win_size: &WinSize,
+        members_max: u32,
+        paths: &[PathKind],
+        sim_time: &SimTime,
+        rng: &mut impl Rng,
+    ) -> Formation {
+        self.join(members_max, || {
+            let (width, height) = (win_size.width, win_size.height);
+
+            // compute the start x/y
+            let w_span = width / 2.0 + ENTRY_MARGIN;
+            let h_span = height / 2.0 + ENTRY_MARGIN;
+            let start_x = if rng.gen_bool(0.5) { w_span } else { -w_span };
+            let start_y = rng.gen_range(-h_span..h_span);
+            let start = (start_x, start_y);
+            // 1 when entering from the left, toward the right
+            let dir = -start_x.signum();
+
+            let kind = paths
+                .get(rng.gen_range(0..paths.len().max(1)))
+                .copied()
+                .unwrap_or(PathKind::Orbit);
+            let path = match kind {
+                PathKind::Orbit => {
+                    // compute the pivot x/y
+                    let w_span = width / 4.;
+                    let h_span = height / 3. + 50.;
+                    let pivot = Vec2::new(
+                        rng.gen_range(span(-w_span, w_span)),
+                        rng.gen_range(0.0..h_span),
+                    );
+
+                    // compute the radious
+                    let radius = Vec2::new(rng.gen_range(80.0..150.0), 100.);
+
+                    FormationPath::Orbit { pivot, radius }
+                }
+                PathKind::GridMarch => {
+                    let (columns, spacing, margin) = (members_max.clamp(1, 4), 50., 40.);
+                    FormationPath::GridMarch {
+                        origin: Vec2::new(
+                            -dir * (width / 2. - margin),
+                            height / 2. - rng.gen_range(80.0..180.0),
+                        ),
+                        columns,
+                        spacing,
+                        // keep the whole row on screen
+                        width: (width - 2. * margin - (columns - 1) as f32 * spacing).max(1.),
+                        step: 20.,
+                        floor: GRID_FLOOR * height,
+                    }
+                }
+                PathKind::SineSweep => FormationPath::SineSweep {
+                    y: rng.gen_range(span(0., height / 3.)),
+                    width: (width - 100.).max(1.),
+                    amplitude: rng.gen_range(30.0..80.0),
+                    wavelength: rng.gen_range(150.0..300.0),
+                },
+                PathKind::FigureEight => FormationPath::FigureEight {
+                    center: Vec2::new(
+                        rng.gen_range(span(-width / 6., width / 6.)),
+                        rng.gen_range(span(height / 8., height / 4.)),
+                    ),
+                    radius: Vec2::new(
+                        rng.gen_range(span(100., width / 3.)),
+                        rng.gen_range(50.0..100.0),
+                    ),
+                },
+                PathKind::Spline => {
+                    let (w_span, h_span) = (width / 2. - 60., height / 2. - 60.);
+                    let count = rng.gen_range(4..=6);
+                    let points = (0..count)
+                        .map(|_| {
+                            Vec2::new(
+                                rng.gen_range(span(-w_span, w_span)),
+                                rng.gen_range(span(0., h_span)),
+                            )
+                        })
+                        .collect();
+                    FormationPath::Spline { points }
+                }
+                PathKind::Dive => FormationPath::Dive {
+                    hold_at: Vec2::new(
+                        rng.gen_range(span(-width / 4., width / 4.)),
+                        rng.gen_range(span(height / 6., height / 3.)),
+                    ),
+                    hold: rng.gen_range(1.5..3.0),
+                },
+                PathKind::Swoop => FormationPath::Swoop {
+                    hold_at: Vec2::new(
+                        rng.gen_range(span(-width / 4., width / 4.)),
+                        rng.gen_range(span(height / 6., height / 3.)),
+                    ),
+                    hold: rng.gen_range(1.0..2.5),
+                    depth: rng.gen_range(span(height / 4., height / 2.)),
+                    width: rng.gen_range(60.0..150.0),
+                },
+            };
+
+            Formation::new(start, path, sim_time.elapsed)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, SeedableRng};
+
+    use super::*;
+
+    const ALL_PATHS: [PathKind; 7] = [
+        PathKind::Orbit,
+        PathKind::GridMarch,
+        PathKind::SineSweep,
+        PathKind::FigureEight,
+        PathKind::Spline,
+        PathKind::Dive,
+        PathKind::Swoop,
+    ];
+
+    #[test]
+    fn small_windows_still_make_formations() {
+        let mut rng = StdRng::seed_from_u64(7);
+        let mut sim_time = SimTime::default();
+
+        for (width, height) in [(0., 0.), (100., 80.), (300., 300.), (598., 676.)] {
+            let win_size = WinSize { width, height };
+            for path in ALL_PATHS {
+                let mut maker = FormationMaker::default();
+                let mut formation = maker.make(&win_size, 1, &[path], &sim_time, &mut rng);
+
+                let mut position = Vec2::from(formation.start);
+                for _ in 0..600 {
+                    sim_time.advance();
+                    position = formation
+                        .step(position, &sim_time, Some(Vec2::ZERO), &win_size)
+                        .0;
+                }
+                assert!(
+                    position.is_finite(),
+                    "{:?} in {}x{} went to {}",
+                    path,
+                    width,
+                    height,
+                    position
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,190 @@
+use bevy::prelude::{Color, Component};
+use serde::Deserialize;
+
+use super::{BulletPattern, FireRule};
+use crate::{components::Armor, powerup::PowerUpKind};
+
+/// Definition of an enemy type, see `EnemyKind::def`
+pub struct EnemyType {
+    pub name: &'static str,
+    pub sprite: &'static str,
+    pub size: (f32, f32), // of the sprite image
+    pub scale: f32,
+    pub color: Color, // tint, so types sharing a sprite can be told apart
+    pub health: f32,
+    pub armor: Option<Armor>,
+    pub score: u32,
+    pub speed: f32, // multiplier of the formation speed
+    pub fire: BulletPattern,
+    pub cooldown: f64, // seconds between two volleys, less in later waves
+    pub jitter: f64,   // fraction of the cooldown a volley may come sooner or later by
+    pub fire_rule: FireRule,
+    pub laser_damage: f32,
+    pub explosion_scale: f32,
+    pub drops: &'static [(PowerUpKind, f64)], // chance of every power-up to drop, at most one drops
+}
+
+const ENEMY_TYPES: [EnemyType; 6] = [
+    EnemyType {
+        name: "scout",
+        sprite: "enemy_a_01.png",
+        size: (144., 75.),
+        scale: 0.4,
+        color: Color::rgb(0.7, 1., 0.7),
+        health: 10.,
+        armor: None,
+        score: 5,
+        speed: 1.2,
+        fire: BulletPattern::Aimed { speed: 0.6 },
+        cooldown: 2.,
+        jitter: 0.3,
+        fire_rule: FireRule::AbovePlayer { reach: 150. },
+        laser_damage: 10.,
+        explosion_scale: 0.8,
+        drops: &[
+            (PowerUpKind::Health, 0.04),
+            (PowerUpKind::Speed, 0.03),
+            (PowerUpKind::Weapon, 0.03),
+        ],
+    },
+    EnemyType {
+        name: "fighter",
+        sprite: "enemy_a_01.png",
+        size: (144., 75.),
+        scale: 0.5,
+        color: Color::WHITE,
+        health: 20.,
+        armor: None,
+        score: 10,
+        speed: 1.,
+        fire: BulletPattern::Burst {
+            count: 3,
+            gap: 0.12,
+            speed: 0.7,
+            aimed: true,
+        },
+        cooldown: 2.5,
+        jitter: 0.3,
+        fire_rule: FireRule::LineOfSight,
+        laser_damage: 10.,
+        explosion_scale: 1.,
+        drops: &[
+            (PowerUpKind::Weapon, 0.06),
+            (PowerUpKind::Shield, 0.04),
+            (PowerUpKind::Health, 0.04),
+            (PowerUpKind::TimeSlow, 0.03),
+        ],
+    },
+    EnemyType {
+        name: "tank",
+        sprite: "enemy_a_01.png",
+        size: (144., 75.),
+        scale: 0.65,
+        color: Color::rgb(1., 0.6, 0.6),
+        health: 30.,
+        armor: Some(Armor {
+            resist: 0.25,
+            flat: 0.,
+        }),
+        score: 20,
+        speed: 0.7,
+        fire: BulletPattern::Homing {
+            speed: 0.35,
+            turn: 2.,
+            fuel: 2.5,
+        },
+        cooldown: 3.5,
+        jitter: 0.2,
+        fire_rule: FireRule::Always,
+        laser_damage: 15.,
+        explosion_scale: 1.5,
+        drops: &[
+            (PowerUpKind::Weapon, 0.1),
+            (PowerUpKind::Shield, 0.06),
+            (PowerUpKind::Health, 0.06),
+            (PowerUpKind::Bomb, 0.05),
+            (PowerUpKind::ExtraLife, 0.03),
+        ],
+    },
+    // the invaders of the classic mode: down in one hit, and down the player in one
+    EnemyType {
+        name: "squid",
+        sprite: "enemy_a_01.png",
+        size: (144., 75.),
+        scale: 0.2,
+        color: Color::rgb(1., 0.5, 1.),
+        health: 10.,
+        armor: None,
+        score: 30,
+        speed: 1.,
+        fire: BulletPattern::Single,
+        cooldown: 0., // the classic grid fires on its own
+        jitter: 0.,
+        fire_rule: FireRule::Always,
+        laser_damage: 100.,
+        explosion_scale: 0.5,
+        drops: &[], // the classic grid drops nothing
+    },
+    EnemyType {
+        name: "crab",
+        sprite: "enemy_a_01.png",
+        size: (144., 75.),
+        scale: 0.24,
+        color: Color::rgb(0.5, 1., 1.),
+        health: 10.,
+        armor: None,
+        score: 20,
+        speed: 1.,
+        fire: BulletPattern::Single,
+        cooldown: 0., // the classic grid fires on its own
+        jitter: 0.,
+        fire_rule: FireRule::Always,
+        laser_damage: 100.,
+        explosion_scale: 0.5,
+        drops: &[], // the classic grid drops nothing
+    },
+    EnemyType {
+        name: "octopus",
+        sprite: "enemy_a_01.png",
+        size: (144., 75.),
+        scale: 0.26,
+        color: Color::rgb(1., 1., 0.5),
+        health: 10.,
+        armor: None,
+        score: 10,
+        speed: 1.,
+        fire: BulletPattern::Single,
+        cooldown: 0., // the classic grid fires on its own
+        jitter: 0.,
+        fire_rule: FireRule::Always,
+        laser_damage: 100.,
+        explosion_scale: 0.5,
+        drops: &[], // the classic grid drops nothing
+    },
+];
+
+/// Component - Type of an enemy, the registry of enemy types is `ENEMY_TYPES`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Component, Deserialize)]
+pub enum EnemyKind {
+    Scout,
+    Fighter,
+    Tank,
+    Squid,
+    Crab,
+    Octopus,
+}
+
+impl EnemyKind {
+    pub const ALL: [EnemyKind; 6] = [
+        EnemyKind::Scout,
+        EnemyKind::Fighter,
+        EnemyKind::Tank,
+        EnemyKind::Squid,
+        EnemyKind::Crab,
+        EnemyKind::Octopus,
+    ];
+
+    pub fn def(&self) -> &'static EnemyType {
+        &ENEMY_TYPES[*self as usize]
+    }
+}
@@ -0,0 +1,536 @@
+use std::fs;
+
+use anyhow::Context;
+use bevy::{
+    asset::{AssetLoader, FileAssetIo, LoadContext, LoadState, LoadedAsset},
+    prelude::*,
+    reflect::TypeUuid,
+    utils::BoxedFuture,
+};
+use serde::Deserialize;
+
+use super::{
+    formation::{Formation, FormationPath, ENTRY_MARGIN, GRID_FLOOR},
+    EnemyKind,
+};
+use crate::{boss::BossKind, config::GameConfig, WinSize};
+
+const ASSETS_FOLDER: &str = "assets";
+
+/// Side of the window a formation enters from
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize)]
+pub enum Side {
+    Left,
+    Right,
+}
+
+/// Path a formation flies once it entered, see `FormationPath`.
+/// Positions are fractions of the window from its center, e.g. (-0.25, 0.2) is left of and above it,
+/// and so are the `width`s of the marches and sweeps; the other sizes are in pixels.
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+pub enum FormationKind {
+    Orbit {
+        pivot: (f32, f32),
+        radius: (f32, f32),
+    },
+    GridMarch {
+        origin: (f32, f32),
+        columns: u32,
+        spacing: f32,
+        width: f32,
+        step: f32,
+    },
+    SineSweep {
+        y: f32,
+        width: f32,
+        amplitude: f32,
+        wavelength: f32,
+    },
+    FigureEight {
+        center: (f32, f32),
+        radius: (f32, f32),
+    },
+    Spline {
+        points: Vec<(f32, f32)>,
+    },
+    Dive {
+        hold_at: (f32, f32),
+        hold: f32,
+    },
+    Swoop {
+        hold_at: (f32, f32),
+        hold: f32,
+        depth: f32,
+        width: f32,
+    },
+}
+
+/// One timed spawn of a level: `count` enemies of one kind, flying the same formation
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+pub struct SpawnEvent {
+    /// seconds to wait after the previous spawn, or after the start of the wave
+    #[serde(default)]
+    pub delay: f64,
+    pub kind: EnemyKind,
+    pub count: u32,
+    /// seconds between two enemies of the event
+    #[serde(default = "default_interval")]
+    pub interval: f64,
+    pub side: Side,
+    /// height the formation enters at, as a fraction of the window from its center
+    #[serde(default)]
+    pub entry_y: f32,
+    pub formation: FormationKind,
+}
+
+fn default_interval() -> f64 {
+    1.
+}
+
+impl FormationKind {
+    /// Rejects the sizes the paths can not fly, which would put the enemies at NaN or infinity.
+    fn check(&self) -> anyhow::Result<()> {
+        let positive = |size: f32| size > 0. && size.is_finite();
+        let finite = |(x, y): (f32, f32)| x.is_finite() && y.is_finite();
+        match self {
+            FormationKind::Orbit { pivot, radius }
+            | FormationKind::FigureEight {
+                center: pivot,
+                radius,
+            } => {
+                anyhow::ensure!(finite(*pivot), "the center is not a position");
+                anyhow::ensure!(
+                    positive(radius.0) && positive(radius.1),
+                    "the radius must be above 0"
+                );
+            }
+            FormationKind::GridMarch {
+                origin,
+                columns,
+                spacing,
+                width,
+                step,
+            } => {
+                anyhow::ensure!(finite(*origin), "the origin is not a position");
+                anyhow::ensure!(*columns > 0, "the columns must be at least 1");
+                anyhow::ensure!(positive(*width), "the width must be above 0");
+                anyhow::ensure!(
+                    *spacing >= 0. && *step >= 0.,
+                    "the spacing and step can not be negative"
+                );
+            }
+            FormationKind::SineSweep {
+                y,
+                width,
+                amplitude,
+                wavelength,
+            } => {
+                anyhow::ensure!(
+                    finite((*y, *amplitude)),
+                    "the height and amplitude must be numbers"
+                );
+                anyhow::ensure!(
+                    positive(*width) && positive(*wavelength),
+                    "the width and wavelength must be above 0"
+                );
+            }
+            FormationKind::Spline { points } => {
+                anyhow::ensure!(points.len() >= 2, "a spline needs at least 2 points");
+                anyhow::ensure!(
+                    points.iter().copied().all(finite),
+                    "a point is not a position"
+                );
+            }
+            FormationKind::Dive { hold_at, hold } => {
+                anyhow::ensure!(finite(*hold_at), "the hold point is not a position");
+                anyhow::ensure!(*hold >= 0., "the hold can not be negative");
+            }
+            FormationKind::Swoop {
+                hold_at,
+                hold,
+                depth,
+                width,
+            } => {
+                anyhow::ensure!(finite(*hold_at), "the hold point is not a position");
+                anyhow::ensure!(
+                    *hold >= 0. && *width >= 0.,
+                    "the hold and width can not be negative"
+                );
+                anyhow::ensure!(positive(*depth), "the depth must be above 0");
+            }
+        }
+        Ok(())
+    }
+}
+
+impl SpawnEvent {
+    fn check(&self) -> anyhow::Result<()> {
+        anyhow::ensure!(self.count > 0, "the count must be at least 1");
+        anyhow::ensure!(
+            self.delay >= 0. && self.delay.is_finite(),
+            "the delay can not be negative"
+        );
+        anyhow::ensure!(
+            self.interval >= 0. && self.interval.is_finite(),
+            "the interval can not be negative"
+        );
+        anyhow::ensure!(
+            self.entry_y.is_finite(),
+            "the entry height must be a number"
+        );
+        self.formation.check()
+    }
+
+    pub fn formation(&self, win_size: &WinSize, started_at: f64) -> Formation {
+        let (width, height) = (win_size.width, win_size.height);
+        let position = |(x, y): (f32, f32)| Vec2::new(x * width, y * height);
+
+        let x = width / 2. + ENTRY_MARGIN;
+        let start = (
+            match self.side {
+                Side::Left => -x,
+                Side::Right => x,
+            },
+            self.entry_y * height,
+        );
+
+        let path = match self.formation.clone() {
+            FormationKind::Orbit { pivot, radius } => FormationPath::Orbit {
+                pivot: position(pivot),
+                radius: radius.into(),
+            },
+            FormationKind::GridMarch {
+                origin,
+                columns,
+                spacing,
+                width: march_width,
+                step,
+            } => FormationPath::GridMarch {
+                origin: position(origin),
+                columns,
+                spacing,
+                width: march_width * width,
+                step,
+                floor: GRID_FLOOR * height,
+            },
+            FormationKind::SineSweep {
+                y,
+                width: sweep_width,
+                amplitude,
+                wavelength,
+            } => FormationPath::SineSweep {
+                y: y * height,
+                width: sweep_width * width,
+                amplitude,
+                wavelength,
+            },
+            FormationKind::FigureEight { center, radius } => FormationPath::FigureEight {
+                center: position(center),
+                radius: radius.into(),
+            },
+            FormationKind::Spline { points } => FormationPath::Spline {
+                points: points.into_iter().map(position).collect(),
+            },
+            FormationKind::Dive { hold_at, hold } => FormationPath::Dive {
+                hold_at: position(hold_at),
+                hold,
+            },
+            FormationKind::Swoop {
+                hold_at,
+                hold,
+                depth,
+                width: swoop_width,
+            } => FormationPath::Swoop {
+                hold_at: position(hold_at),
+                hold,
+                depth,
+                width: swoop_width,
+            },
+        };
+
+        Formation::new(start, path, started_at)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+pub struct LevelWave {
+    /// seconds to clear the wave in (from its start) for a time bonus
+    pub par_time: f64,
+    pub events: Vec<SpawnEvent>,
+    /// seconds into the wave a UFO flies at, none if empty; without a level it flies at random
+    #[serde(default)]
+    pub ufos: Vec<f64>,
+    /// fought once the events are down
+    #[serde(default)]
+    pub boss: Option<BossKind>,
+}
+
+/// Asset - Scripted level, a `.level.ron` file under `assets/levels`, e.g.
+/// ```text
+/// (
+///     name: "First Contact",
+///     waves: [
+///         (
+///             par_time: 30.,
+///             events: [
+///                 (kind: Scout, count: 3, interval: 0.5, side: Left, entry_y: 0.1,
+///                  formation: Orbit(pivot: (-0.2, 0.2), radius: (120., 100.))),
+///                 (delay: 2., kind: Fighter, count: 2, side: Right,
+///                  formation: Orbit(pivot: (0.2, 0.25), radius: (90., 100.))),
+///             ],
+///         ),
+///     ],
+/// )
+/// ```
+/// The waves play in order; once through, the last one repeats at a higher difficulty.
+#[derive(Clone, Debug, PartialEq, Deserialize, TypeUuid)]
+#[uuid = "e534796b-b1aa-482f-b1b7-c925eb59e23c"]
+pub struct Level {
+    pub name: String,
+    pub waves: Vec<LevelWave>,
+}
+
+impl Level {
+    pub fn parse(bytes: &[u8]) -> anyhow::Result<Self> {
+        let level: Level = ron::de::from_bytes(bytes)?;
+        anyhow::ensure!(
+            !level.waves.is_empty(),
+            "level `{}` has no waves",
+            level.name
+        );
+        // checked up front, so a hot reload keeps the last good level instead of a broken one
+        for (w, wave) in level.waves.iter().enumerate() {
+            for (e, event) in wave.events.iter().enumerate() {
+                event.check().map_err(|err| {
+                    anyhow::anyhow!(
+                        "level `{}`, wave {} event {}: {}",
+                        level.name,
+                        w + 1,
+                        e + 1,
+                        err
+                    )
+                })?;
+            }
+        }
+        Ok(level)
+    }
+
+    /// Reads the level at `path`, relative to the assets folder, straight from the disk.
+    /// The folder is found the way the asset server finds it, next to the manifest under cargo
+    /// and next to the executable otherwise, so it does not depend on the working directory.
+    pub fn load(path: &str) -> anyhow::Result<Self> {
+        let full_path = FileAssetIo::get_root_path().join(ASSETS_FOLDER).join(path);
+        let bytes = fs::read(&full_path)
+            .with_context(|| format!("could not read {}", full_path.display()))?;
+        Self::parse(&bytes)
+    }
+}
+
+#[derive(Default)]
+pub struct LevelLoader;
+
+impl AssetLoader for LevelLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, anyhow::Result<()>> {
+        Box::pin(async move {
+            load_context.set_default_asset(LoadedAsset::new(Level::parse(bytes)?));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["level.ron"]
+    }
+}
+
+// region: --- Resources
+
+/// Resource - Scripted level being played, if any.
+/// While one is active it decides what spawns, instead of the built-in waves.
+#[derive(Default)]
+pub struct LevelRunner {
+    handle: Option<Handle<Level>>, // to pick up hot reloads, windowed only
+    level: Option<Level>,
+}
+
+impl LevelRunner {
+    pub fn loaded(level: Level) -> Self {
+        Self {
+            handle: None,
+            level: Some(level),
+        }
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.handle.is_some() || self.level.is_some()
+    }
+
+    /// Is the level still on its way from the asset server?
+    pub fn is_loading(&self) -> bool {
+        self.handle.is_some() && self.level.is_none()
+    }
+
+    /// The wave `number` (from 1) of the level.
+    pub fn wave(&self, number: u32) -> Option<&LevelWave> {
+        let waves = &self.level.as_ref()?.waves;
+        waves.get(number as usize - 1).or_else(|| waves.last())
+    }
+
+    /// The event the enemy `index` of wave `number` spawns from, and its index in that event.
+    pub fn event_at(&self, number: u32, mut index: u32) -> Option<(&SpawnEvent, u32)> {
+        for event in &self.wave(number)?.events {
+            if index < event.count {
+                return Some((event, index));
+            }
+            index -= event.count;
+        }
+        None
+    }
+}
+
+// endregion: --- Resources
+
+/// Loads the level given with `--level` through the asset server in a window, so editing
+/// the file reloads it. Headless, `headless::run` loads it straight from the disk before the
+/// app is built, so the match does not depend on how long the loading takes, and a level that
+/// does not load fails the run.
+pub struct LevelPlugin;
+
+impl Plugin for LevelPlugin {
+    fn build(&self, app: &mut App) {
+        let path = app.world.resource::<GameConfig>().level.clone();
+        match path {
+            Some(path) if app.world.contains_resource::<AssetServer>() => {
+                let handle = app.world.resource::<AssetServer>().load(path.as_str());
+                app.add_asset::<Level>()
+                    .init_asset_loader::<LevelLoader>()
+                    .add_system(level_reload_system)
+                    .insert_resource(LevelRunner {
+                        handle: Some(handle),
+                        level: None,
+                    });
+            }
+            _ => {
+                app.init_resource::<LevelRunner>();
+            }
+        }
+    }
+}
+
+/// Picks up the level once it is loaded, and again every time its file changes.
+fn level_reload_system(
+    mut events: EventReader<AssetEvent<Level>>,
+    levels: Res<Assets<Level>>,
+    asset_server: Res<AssetServer>,
+    mut runner: ResMut<LevelRunner>,
+) {
+    let handle = match &runner.handle {
+        Some(handle) => handle.clone(),
+        None => return,
+    };
+
+    for event in events.iter() {
+        match event {
+            AssetEvent::Created { handle: changed } | AssetEvent::Modified { handle: changed }
+                if *changed == handle =>
+            {
+                if let Some(level) = levels.get(changed) {
+                    println!("level `{}` loaded", level.name);
+                    runner.level = Some(level.clone());
+                }
+            }
+            _ => {}
+        }
+    }
+
+    if runner.level.is_none() && asset_server.get_load_state(&handle) == LoadState::Failed {
+        eprintln!("error: could not load level, playing the built-in waves");
+        runner.handle = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A one wave level with `event` as its second event
+    fn level_with(event: &str) -> String {
+        format!(
+            "(name: \"Test\", waves: [(par_time: 30., events: [
+                (kind: Scout, count: 3, side: Left, formation: Orbit(pivot: (0., 0.2), radius: (120., 100.))),
+                {},
+            ])])",
+            event
+        )
+    }
+
+    fn error_of(event: &str) -> String {
+        Level::parse(level_with(event).as_bytes())
+            .unwrap_err()
+            .to_string()
+    }
+
+    #[test]
+    fn parses_the_first_level() {
+        let level = Level::parse(include_bytes!("../../assets/levels/first.level.ron")).unwrap();
+        assert!(!level.waves.is_empty());
+    }
+
+    #[test]
+    fn rejects_unflyable_formations() {
+        assert!(Level::parse(
+            level_with("(kind: Tank, count: 1, side: Right, formation: Dive(hold_at: (0., 0.3), hold: 2.))").as_bytes()
+        )
+        .is_ok());
+
+        let rejected = [
+            "(kind: Scout, count: 1, side: Left, formation: Orbit(pivot: (0., 0.), radius: (0., 100.)))",
+            "(kind: Scout, count: 1, side: Left, formation: FigureEight(center: (0., 0.), radius: (80., -1.)))",
+            "(kind: Scout, count: 1, side: Left, formation: GridMarch(origin: (0., 0.3), columns: 0, spacing: 40., width: 0.5, step: 20.))",
+            "(kind: Scout, count: 1, side: Left, formation: GridMarch(origin: (0., 0.3), columns: 4, spacing: 40., width: 0., step: 20.))",
+            "(kind: Scout, count: 1, side: Left, formation: SineSweep(y: 0.2, width: 0.8, amplitude: 40., wavelength: 0.))",
+            "(kind: Scout, count: 1, side: Left, formation: SineSweep(y: 0.2, width: -0.5, amplitude: 40., wavelength: 200.))",
+            "(kind: Scout, count: 1, side: Left, formation: Spline(points: [(0., 0.)]))",
+            "(kind: Scout, count: 1, side: Left, formation: Dive(hold_at: (0., 0.3), hold: -1.))",
+            "(kind: Scout, count: 1, side: Left, formation: Swoop(hold_at: (0., 0.3), hold: 1., depth: 0., width: 80.))",
+        ];
+        for event in rejected {
+            assert!(
+                error_of(event).starts_with("level `Test`, wave 1 event 2: "),
+                "{} was accepted",
+                event
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_bad_spawns() {
+        let orbit = "formation: Orbit(pivot: (0., 0.), radius: (100., 100.))";
+        assert_eq!(
+            error_of(&format!("(kind: Scout, count: 0, side: Left, {})", orbit)),
+            "level `Test`, wave 1 event 2: the count must be at least 1"
+        );
+        assert_eq!(
+            error_of(&format!(
+                "(delay: -1., kind: Scout, count: 2, side: Left, {})",
+                orbit
+            )),
+            "level `Test`, wave 1 event 2: the delay can not be negative"
+        );
+        assert_eq!(
+            error_of(&format!(
+                "(kind: Scout, count: 2, interval: -0.5, side: Left, {})",
+                orbit
+            )),
+            "level `Test`, wave 1 event 2: the interval can not be negative"
+        );
+        // unknown sides do not even deserialize
+        assert!(Level::parse(
+            level_with(&format!("(kind: Scout, count: 2, side: Up, {})", orbit)).as_bytes()
+        )
+        .is_err());
+    }
+}
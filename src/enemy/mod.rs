@@ -1,150 +1,457 @@
+use self::classic::{
+    classic_fire_system, classic_invasion_system, classic_march_system, classic_spawn_system,
+    ClassicMarch,
+};
 use self::formation::{Formation, FormationMaker};
-use crate::components::{Enemy, FromEnemy, Laser, Movable, SpriteSize, Velocity};
+use self::level::LevelPlugin;
+use self::pattern::{bullet_rotation_system, homing_system};
+use crate::boss::BossKind;
+use crate::components::{
+    Attributes, Damage, Enemy, FromEnemy, Laser, Movable, SpriteSize, Velocity,
+};
+use crate::components::{Interpolated, Player};
 use crate::{
-	EnemyCount, GameTextures, WinSize, ENEMY_LASER_SIZE, MAX_ENEMY_COUNT, ENEMY_SIZE, SPRITE_SCALE,
-	TIME_STEP, AppState, GameSounds,
+    in_game, AppState, EnemyCount, GameMode, GameRng, GameSounds, GameState, GameTextures,
+    SimLabel, SimTime, SimulationStage, WinSize, ENEMY_LASER_SIZE, MAX_ENEMY_COUNT, SPRITE_SCALE,
+    TIME_STEP,
 };
-use bevy::core::FixedTimestep;
-use bevy::ecs::schedule::ShouldRun;
+use bevy::math::Vec3Swizzles;
 use bevy::prelude::*;
-use rand::{thread_rng, Rng};
-use std::f32::consts::PI;
+use rand::Rng;
 
 use bevy_kira_audio::{Audio, AudioPlugin};
 
+mod classic;
 mod formation;
+mod kinds;
+mod level;
+mod pattern;
+mod wave;
+
+pub use classic::Invader;
+pub use kinds::EnemyKind;
+pub use level::{Level, LevelRunner};
+pub use pattern::{bullet_rotation, spawn_shot, steer, BulletPattern, FireRule, Gun};
+pub use wave::{Wave, WavePhase};
 
 pub struct EnemyPlugin;
 
 impl Plugin for EnemyPlugin {
-	fn build(&self, app: &mut App) {
-		app.insert_resource(FormationMaker::default())
-			.add_system_set(
-				SystemSet::new()
-					.with_run_criteria(FixedTimestep::step(1.))
-					.with_system(enemy_spawn_system),
-			)
-            .add_system_set(
-                SystemSet::on_update(AppState::InGame) // Only run this system when in the InGame state
-                .with_system(enemy_movement_system),
+    fn build(&self, app: &mut App) {
+        app.add_plugin(LevelPlugin)
+            .insert_resource(FormationMaker::default())
+            .insert_resource(Wave::default())
+            .insert_resource(ClassicMarch::default())
+            .add_system_set_to_stage(
+                SimulationStage,
+                SystemSet::new()
+                    .with_run_criteria(in_game)
+                    .label(SimLabel::Spawn)
+                    .after(SimLabel::Clock)
+                    .with_system(enemy_spawn_system)
+                    .with_system(level_spawn_system)
+                    .with_system(classic_spawn_system),
+            )
+            .add_system_set_to_stage(
+                SimulationStage,
+                SystemSet::new()
+                    .with_run_criteria(in_game)
+                    .label(SimLabel::Effects)
+                    .after(SimLabel::Collision)
+                    .with_system(wave_system),
+            )
+            .add_system_set(SystemSet::on_enter(AppState::InGame).with_system(enemy_reset_system))
+            .add_system_set_to_stage(
+                SimulationStage,
+                SystemSet::new()
+                    .with_run_criteria(in_game) // Only run this system when in the InGame state
+                    .label(SimLabel::Movement)
+                    .after(SimLabel::Fire)
+                    .with_system(enemy_movement_system)
+                    .with_system(classic_march_system)
+                    .with_system(bullet_rotation_system),
             )
-			.add_system_set(
-				SystemSet::on_update(AppState::InGame) // Only run this system when in the InGame state
-					.with_run_criteria(enemy_fire_criteria)
-					.with_system(enemy_fire_system),
-			);
-	}
+            .add_system_set_to_stage(
+                SimulationStage,
+                SystemSet::new()
+                    .with_run_criteria(in_game)
+                    .label(SimLabel::Collision)
+                    .after(SimLabel::Movement)
+                    .with_system(classic_invasion_system),
+            )
+            .add_system_set_to_stage(
+                SimulationStage,
+                SystemSet::new()
+                    .with_run_criteria(in_game)
+                    .label(SimLabel::Fire)
+                    .after(SimLabel::Input)
+                    .with_system(enemy_fire_system)
+                    .with_system(classic_fire_system)
+                    .with_system(homing_system),
+            );
+    }
 }
 
+/// Spawns the roster of the current wave, one enemy every `spawn_interval`.
 fn enemy_spawn_system(
-	mut commands: Commands,
-	game_textures: Res<GameTextures>,
-	mut enemy_count: ResMut<EnemyCount>,
-	mut formation_maker: ResMut<FormationMaker>,
-	win_size: Res<WinSize>,
+    mut commands: Commands,
+    game_textures: Res<GameTextures>,
+    mut enemy_count: ResMut<EnemyCount>,
+    mut formation_maker: ResMut<FormationMaker>,
+    mut rng: ResMut<GameRng>,
+    mut wave: ResMut<Wave>,
+    level_runner: Res<LevelRunner>,
+    game_mode: Res<GameMode>,
+    sim_time: Res<SimTime>,
+    win_size: Res<WinSize>,
 ) {
-	if enemy_count.0 < MAX_ENEMY_COUNT {
-		// get formation and start x/y
-		let formation = formation_maker.make(&win_size);
-		let (x, y) = formation.start;
-
-		commands
-			.spawn_bundle(SpriteBundle {
-				texture: game_textures.enemy.clone(),
-				transform: Transform {
-					translation: Vec3::new(x, y, 10.),
-					scale: Vec3::new(SPRITE_SCALE, SPRITE_SCALE, 1.),
-					..Default::default()
-				},
-				..Default::default()
-			})
-			.insert(Enemy)
-			.insert(formation)
-			.insert(SpriteSize::from(ENEMY_SIZE));
-
-		enemy_count.0 += 1;
-	}
+    // a scripted level or the classic grid spawns its own enemies
+    if level_runner.is_active() || *game_mode == GameMode::Classic {
+        return;
+    }
+
+    let due =
+        matches!(wave.phase, WavePhase::Spawning { next_spawn } if sim_time.elapsed >= next_spawn);
+    if let Some(kind) = wave
+        .next_kind()
+        .filter(|_| due && enemy_count.0 < MAX_ENEMY_COUNT)
+    {
+        // get formation and start x/y
+        let def = wave.def();
+        let formation = formation_maker.make(
+            &win_size,
+            def.formation_size,
+            def.paths,
+            &sim_time,
+            &mut rng.0,
+        );
+        spawn_enemy(
+            &mut commands,
+            &game_textures,
+            &mut rng,
+            kind,
+            formation,
+            wave.difficulty(),
+            &sim_time,
+        );
+        enemy_count.0 += 1;
+
+        let next_delay = wave
+            .kind_at(wave.spawned + 1)
+            .map(|_| wave.def().spawn_interval);
+        wave.spawned(&sim_time, next_delay);
+    }
 }
 
-fn enemy_fire_criteria() -> ShouldRun {
-	if thread_rng().gen_bool(1. / 60.) {
-		ShouldRun::Yes
-	} else {
-		ShouldRun::No
-	}
+/// Spawns the events of the current wave of the scripted level, if one is active.
+fn level_spawn_system(
+    mut commands: Commands,
+    game_textures: Res<GameTextures>,
+    mut enemy_count: ResMut<EnemyCount>,
+    mut formation_maker: ResMut<FormationMaker>,
+    mut rng: ResMut<GameRng>,
+    mut wave: ResMut<Wave>,
+    level_runner: Res<LevelRunner>,
+    game_mode: Res<GameMode>,
+    sim_time: Res<SimTime>,
+    win_size: Res<WinSize>,
+) {
+    let due =
+        matches!(wave.phase, WavePhase::Spawning { next_spawn } if sim_time.elapsed >= next_spawn);
+    let active = level_runner.is_active() && *game_mode != GameMode::Classic;
+    if !active || !due || enemy_count.0 >= MAX_ENEMY_COUNT {
+        return;
+    }
+
+    match level_runner.event_at(wave.number, wave.spawned) {
+        Some((event, index)) => {
+            // every event flies its own formation
+            if index == 0 {
+                *formation_maker = FormationMaker::default();
+            }
+            let formation =
+                formation_maker.join(event.count, || event.formation(&win_size, sim_time.elapsed));
+            spawn_enemy(
+                &mut commands,
+                &game_textures,
+                &mut rng,
+                event.kind,
+                formation,
+                wave.difficulty(),
+                &sim_time,
+            );
+            enemy_count.0 += 1;
+
+            // the first enemy of the next event waits for its delay, the others for the interval
+            let next_delay =
+                level_runner
+                    .event_at(wave.number, wave.spawned + 1)
+                    .map(|(next, index)| {
+                        if index == 0 {
+                            next.delay
+                        } else {
+                            next.interval
+                        }
+                    });
+            wave.spawned(&sim_time, next_delay);
+        }
+        // a reload took the rest of the wave away
+        None => wave.phase = WavePhase::Fighting,
+    }
 }
 
+fn spawn_enemy(
+    commands: &mut Commands,
+    game_textures: &GameTextures,
+    rng: &mut GameRng,
+    kind: EnemyKind,
+    mut formation: Formation,
+    difficulty: f32,
+    sim_time: &SimTime,
+) {
+    let (x, y) = formation.start;
+    let enemy_type = kind.def();
+    formation.speed *= enemy_type.speed * difficulty;
+
+    // the first volley comes sooner, many enemies do not live through a whole cooldown
+    let mut gun = Gun::new(0., enemy_type.jitter);
+    gun.next_shot =
+        sim_time.elapsed + gun.jittered(enemy_type.cooldown / 2. / difficulty as f64, &mut rng.0);
+
+    let enemy = commands
+        .spawn_bundle(SpriteBundle {
+            texture: game_textures
+                .enemies
+                .get(&kind)
+                .cloned()
+                .unwrap_or_default(),
+            sprite: Sprite {
+                color: enemy_type.color,
+                ..Default::default()
+            },
+            transform: Transform {
+                translation: Vec3::new(x, y, 10.),
+                scale: Vec3::new(enemy_type.scale, enemy_type.scale, 1.),
+                ..Default::default()
+            },
+            ..Default::default()
+        })
+        .insert(Enemy)
+        .insert(kind)
+        .insert(gun)
+        .insert(formation)
+        .insert(SpriteSize::from(enemy_type.size))
+        .insert(Attributes {
+            health: enemy_type.health,
+            max_health: enemy_type.health,
+        })
+        .id();
+    if let Some(armor) = enemy_type.armor {
+        commands.entity(enemy).insert(armor);
+    }
+}
+
+/// Starts the wave after its intermission, and moves on to the next one once it is cleared.
+fn wave_system(
+    mut wave: ResMut<Wave>,
+    mut formation_maker: ResMut<FormationMaker>,
+    mut game_state: ResMut<GameState>,
+    enemy_count: Res<EnemyCount>,
+    level_runner: Res<LevelRunner>,
+    game_mode: Res<GameMode>,
+    sim_time: Res<SimTime>,
+) {
+    // the boss of the wave, if any, comes in once the rest is down, see `boss_spawn_system`
+    let boss_pending = !wave.boss_spawned && wave_boss(&wave, &level_runner, *game_mode).is_some();
+    // the classic grid ignores the level
+    let level_runner = Some(&*level_runner).filter(|_| *game_mode != GameMode::Classic);
+
+    // only borrow mutably when there is something to do, the HUD watches for changes
+    // (and hold the wave back until the level is loaded)
+    if wave.in_intermission() && !level_runner.is_some_and(|runner| runner.is_loading()) {
+        let first_delay = level_runner
+            .and_then(|runner| runner.event_at(wave.number, 0))
+            .map_or(0., |(event, _)| event.delay);
+        wave.update(&sim_time, first_delay);
+    }
+
+    if wave.phase == WavePhase::Fighting && enemy_count.0 == 0 && !boss_pending {
+        let number = wave.number;
+        let par_time = match (
+            *game_mode,
+            level_runner.and_then(|runner| runner.wave(number)),
+        ) {
+            (GameMode::Classic, _) => classic::PAR_TIME,
+            (_, Some(level_wave)) => level_wave.par_time,
+            _ => wave.def().par_time,
+        };
+        let bonus = wave.clear(&sim_time, par_time);
+        // the next wave starts with a new formation
+        *formation_maker = FormationMaker::default();
+        game_state.score += bonus.time + bonus.perfect;
+    }
+}
+
+/// The boss at the end of the current wave, if any. The classic mode has none.
+pub fn wave_boss(wave: &Wave, level_runner: &LevelRunner, game_mode: GameMode) -> Option<BossKind> {
+    match game_mode {
+        GameMode::Classic => None,
+        _ if level_runner.is_active() => level_runner
+            .wave(wave.number)
+            .and_then(|level_wave| level_wave.boss),
+        _ => wave.boss(),
+    }
+}
+
+fn enemy_reset_system(mut formation_maker: ResMut<FormationMaker>, mut wave: ResMut<Wave>) {
+    *formation_maker = FormationMaker::default();
+    *wave = Wave::default();
+}
+
+/// Fires the pattern of every enemy whose gun is due, as long as it is on screen and its fire rule
+/// allows it. Past the bullet cap of the wave, the guns wait for bullets to leave the screen.
 fn enemy_fire_system(
-	mut commands: Commands,
-	game_textures: Res<GameTextures>,
+    mut commands: Commands,
+    game_textures: Res<GameTextures>,
     game_sounds: Res<GameSounds>,
-	enemy_query: Query<&Transform, With<Enemy>>,
+    mut rng: ResMut<GameRng>,
+    sim_time: Res<SimTime>,
+    win_size: Res<WinSize>,
+    wave: Res<Wave>,
+    // the classic grid has no guns, see `classic_fire_system`
+    mut enemy_query: Query<(Entity, &Transform, &EnemyKind, &mut Gun), With<Enemy>>,
+    blocker_query: Query<(Entity, &Transform, &SpriteSize), With<Enemy>>,
+    bullet_query: Query<(), (With<Laser>, With<FromEnemy>)>,
+    player_query: Query<&Transform, With<Player>>,
     audio: Res<Audio>,
 ) {
-	for &tf in enemy_query.iter() {
-		let (x, y) = (tf.translation.x, tf.translation.y);
-		// spawn enemy laser sprite
-		commands
-			.spawn_bundle(SpriteBundle {
-				texture: game_textures.enemy_laser.clone(),
-				transform: Transform {
-					translation: Vec3::new(x, y - 15., 0.),
-					rotation: Quat::from_rotation_x(PI),
-					scale: Vec3::new(SPRITE_SCALE, SPRITE_SCALE, 1.),
-					..Default::default()
-				},
-				..Default::default()
-			})
-			.insert(Laser)
-			.insert(SpriteSize::from(ENEMY_LASER_SIZE))
-			.insert(FromEnemy)
-			.insert(Movable { auto_despawn: true })
-			.insert(Velocity { x: 0., y: -1. });
+    let player = player_query
+        .get_single()
+        .ok()
+        .map(|transform| transform.translation.xy());
+    let (half_width, half_height) = (win_size.width / 2., win_size.height / 2.);
+    let mut bullets = bullet_query.iter().count();
+
+    for (entity, tf, kind, mut gun) in enemy_query.iter_mut() {
+        let position = tf.translation.xy();
+        let on_screen = position.x.abs() < half_width && position.y.abs() < half_height;
+        if sim_time.elapsed < gun.next_shot || !on_screen {
+            continue;
+        }
+        // a volley only starts below the cap, and may take the bullets a little past it
+        if bullets >= wave.bullet_cap() {
+            break;
+        }
+
+        let enemy_type = kind.def();
+        let origin = position - Vec2::new(0., 15.);
+        // the other enemies, including the boss and its zones, are in the way
+        let blockers = blocker_query
+            .iter()
+            .filter(|(other, ..)| *other != entity)
+            .map(|(_, tf, size)| (tf.translation.xy(), size.0 * tf.scale.xy()));
+        if !enemy_type.fire_rule.allows(origin, player, blockers) {
+            continue;
+        }
+
+        let cooldown = enemy_type.cooldown / wave.difficulty() as f64;
+        gun.fire(
+            enemy_type.fire,
+            origin,
+            player,
+            &sim_time,
+            cooldown,
+            &mut rng.0,
+            |shot| {
+                bullets += 1;
+                spawn_shot(
+                    &mut commands,
+                    &game_textures,
+                    &sim_time,
+                    origin,
+                    shot,
+                    enemy_type.laser_damage,
+                );
+            },
+        );
 
         // Playing the laser sound
         audio.play(game_sounds.enemy_laser.clone());
-	}
+    }
 }
 
-fn enemy_movement_system(mut query: Query<(&mut Transform, &mut Formation), With<Enemy>>) {
-	for (mut transform, mut formation) in query.iter_mut() {
-		// current position
-		let (x_org, y_org) = (transform.translation.x, transform.translation.y);
-
-		// max distance
-		let max_distance = TIME_STEP * formation.speed;
-
-		// 1 for counter clockwise, -1 clockwise
-		let dir: f32 = if formation.start.0 < 0. { 1. } else { -1. };
-		let (x_pivot, y_pivot) = formation.pivot;
-		let (x_radius, y_radius) = formation.radius;
-
-		// compute next angle (based on time for now)
-		let angle = formation.angle
-			+ dir * formation.speed * TIME_STEP / (x_radius.min(y_radius) * PI / 2.);
-
-		// compute target x/y
-		let x_dst = x_radius * angle.cos() + x_pivot;
-		let y_dst = y_radius * angle.sin() + y_pivot;
-
-		// compute distance
-		let dx = x_org - x_dst;
-		let dy = y_org - y_dst;
-		let distance = (dx * dx + dy * dy).sqrt();
-		let distance_ratio = if distance != 0. { max_distance / distance } else { 0. };
-
-		// compute final x/y
-		let x = x_org - dx * distance_ratio;
-		let x = if dx > 0. { x.max(x_dst) } else { x.min(x_dst) };
-		let y = y_org - dy * distance_ratio;
-		let y = if dy > 0. { y.max(y_dst) } else { y.min(y_dst) };
-
-		// start rotating the formation angle only when sprite is on or close to ellipse
-		if distance < max_distance * formation.speed / 20. {
-			formation.angle = angle;
-		}
-
-		let translation = &mut transform.translation;
-		(translation.x, translation.y) = (x, y);
-	}
+/// Spawns an enemy laser going down from the enemy at `x`/`y`.
+fn spawn_enemy_laser(
+    commands: &mut Commands,
+    game_textures: &GameTextures,
+    x: f32,
+    y: f32,
+    damage: f32,
+) {
+    spawn_enemy_bullet(
+        commands,
+        game_textures,
+        Vec2::new(x, y - 15.),
+        Vec2::new(0., -1.),
+        damage,
+    );
+}
+
+/// Spawns an enemy laser at `position`, flying at `velocity` (a multiplier of the base speed)
+/// and turned the way it flies.
+pub fn spawn_enemy_bullet(
+    commands: &mut Commands,
+    game_textures: &GameTextures,
+    position: Vec2,
+    velocity: Vec2,
+    damage: f32,
+) -> Entity {
+    commands
+        .spawn_bundle(SpriteBundle {
+            texture: game_textures.enemy_laser.clone(),
+            transform: Transform {
+                translation: position.extend(0.),
+                rotation: bullet_rotation(velocity),
+                scale: Vec3::new(SPRITE_SCALE, SPRITE_SCALE, 1.),
+            },
+            ..Default::default()
+        })
+        .insert(Laser)
+        .insert(SpriteSize::from(ENEMY_LASER_SIZE))
+        .insert(FromEnemy)
+        .insert(Damage(damage))
+        .insert(Movable { auto_despawn: true })
+        .insert(Velocity {
+            x: velocity.x,
+            y: velocity.y,
+        })
+        .id()
+}
+
+fn enemy_movement_system(
+    sim_time: Res<SimTime>,
+    win_size: Res<WinSize>,
+    player_query: Query<&Transform, With<Player>>,
+    mut query: Query<
+        (&mut Transform, &mut Formation, Option<&mut Interpolated>),
+        (With<Enemy>, Without<Player>),
+    >,
+) {
+    let player = player_query
+        .get_single()
+        .ok()
+        .map(|transform| transform.translation.xy());
+
+    for (mut transform, mut formation, interpolated) in query.iter_mut() {
+        let (position, jumped) =
+            formation.step(transform.translation.xy(), &sim_time, player, &win_size);
+
+        // do not draw the jump back to the top
+        if let (true, Some(mut interpolated)) = (jumped, interpolated) {
+            interpolated.previous = position;
+        }
+
+        let translation = &mut transform.translation;
+        (translation.x, translation.y) = position.into();
+    }
 }
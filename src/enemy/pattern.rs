@@ -0,0 +1,334 @@
+use std::f32::consts::{PI, TAU};
+
+use bevy::{
+    math::{Mat2, Vec3Swizzles},
+    prelude::*,
+};
+
+use rand::Rng;
+
+use super::spawn_enemy_bullet;
+use crate::{
+    components::{FromEnemy, Laser, Player, Velocity},
+    GameTextures, SimTime, TIME_STEP,
+};
+
+const DOWN: f32 = -PI / 2.;
+const MISSILE_COLOR: Color = Color::rgb(1., 0.6, 0.2);
+
+/// How an enemy or a boss fires, speeds are multipliers of the base speed and angles in radians
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BulletPattern {
+    /// one laser straight down
+    Single,
+    /// one shot at the player
+    Aimed { speed: f32 },
+    /// `count` shots fanned out over `arc`, around straight down, or around the player if `aimed`
+    Spread {
+        count: u32,
+        arc: f32,
+        speed: f32,
+        aimed: bool,
+    },
+    /// `arms` shots evenly all around, turned by `spin` every volley
+    Spiral { arms: u32, spin: f32, speed: f32 },
+    /// `count` shots `gap` seconds apart, straight down, or at where the player was when it started if `aimed`
+    Burst {
+        count: u32,
+        gap: f64,
+        speed: f32,
+        aimed: bool,
+    },
+    /// a stream of shots `gap` seconds apart, sweeping over `arc` and back in `period` seconds
+    Beam {
+        arc: f32,
+        period: f64,
+        gap: f64,
+        speed: f32,
+    },
+    /// a missile turning towards the player at up to `turn` radians per second, for `fuel` seconds
+    Homing { speed: f32, turn: f32, fuel: f64 },
+}
+
+/// When an enemy may fire, once its gun is due
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FireRule {
+    /// whenever on screen
+    Always,
+    /// only above the player, and within `reach` pixels of it sideways
+    AbovePlayer { reach: f32 },
+    /// only with no other enemy in the way to the player
+    LineOfSight,
+}
+
+impl FireRule {
+    /// May an enemy at `origin` fire at the `player`, with `blockers` (center and size) around?
+    pub fn allows(
+        &self,
+        origin: Vec2,
+        player: Option<Vec2>,
+        mut blockers: impl Iterator<Item = (Vec2, Vec2)>,
+    ) -> bool {
+        match (self, player) {
+            (FireRule::Always, _) => true,
+            (_, None) => false,
+            (FireRule::AbovePlayer { reach }, Some(player)) => {
+                player.y < origin.y && (player.x - origin.x).abs() <= *reach
+            }
+            (FireRule::LineOfSight, Some(player)) => {
+                !blockers.any(|(center, size)| segment_hits_box(origin, player, center, size / 2.))
+            }
+        }
+    }
+}
+
+/// Does the segment from `a` to `b` go through the box?
+fn segment_hits_box(a: Vec2, b: Vec2, center: Vec2, half_size: Vec2) -> bool {
+    // clip the segment to the slabs of the box, one axis at a time
+    let (mut enter, mut exit) = (0f32, 1f32);
+    for axis in 0..2 {
+        let (from, delta) = (a[axis] - center[axis], b[axis] - a[axis]);
+        if delta == 0. {
+            if from.abs() > half_size[axis] {
+                return false;
+            }
+            continue;
+        }
+        let (t0, t1) = (
+            (-half_size[axis] - from) / delta,
+            (half_size[axis] - from) / delta,
+        );
+        enter = enter.max(t0.min(t1));
+        exit = exit.min(t0.max(t1));
+        if enter > exit {
+            return false;
+        }
+    }
+    true
+}
+
+/// One bullet of a volley
+pub struct Shot {
+    pub velocity: Vec2,
+    pub homing: Option<(f32, f64)>, // turn and fuel, see `BulletPattern::Homing`
+}
+
+// region: --- Components
+
+/// Component - Fire state of an enemy or a boss, see `Gun::fire`
+#[derive(Component, Clone, Debug)]
+pub struct Gun {
+    pub next_shot: f64, // sim time
+    jitter: f64,        // fraction of the cooldown the next volley may come sooner or later by
+    volley: u32,        // fired so far, spins the spirals, sweeps the beams and counts the bursts
+    aim: Vec2,          // direction of the current burst
+}
+
+/// Component - Missile steering towards the player, until the sim time
+#[derive(Component)]
+pub struct Homing {
+    turn: f32,
+    until: f64,
+}
+
+// endregion: --- Components
+
+impl Gun {
+    pub fn new(next_shot: f64, jitter: f64) -> Self {
+        Self {
+            next_shot,
+            jitter,
+            volley: 0,
+            aim: Vec2::new(0., -1.),
+        }
+    }
+
+    /// Starts over with the first volley of a pattern, at the sim time.
+    pub fn reset(&mut self, next_shot: f64) {
+        *self = Self::new(next_shot, self.jitter);
+    }
+
+    /// The cooldown, give or take the jitter.
+    pub fn jittered(&self, cooldown: f64, rng: &mut impl Rng) -> f64 {
+        if self.jitter > 0. {
+            cooldown * (1. + rng.gen_range(-self.jitter..=self.jitter))
+        } else {
+            cooldown
+        }
+    }
+
+    /// Fires the next volley of the pattern from `origin` through `shoot`, and schedules the one after:
+    /// `cooldown` seconds later (give or take the jitter), or sooner in the middle of a burst or a beam.
+    pub fn fire(
+        &mut self,
+        pattern: BulletPattern,
+        origin: Vec2,
+        player: Option<Vec2>,
+        sim_time: &SimTime,
+        cooldown: f64,
+        rng: &mut impl Rng,
+        mut shoot: impl FnMut(Shot),
+    ) {
+        let cooldown = self.jittered(cooldown, rng);
+        let at_player = player
+            .and_then(|player| (player - origin).try_normalize())
+            .map_or(DOWN, |aim| aim.y.atan2(aim.x));
+        let mut fire = |angle: f32, speed: f32| {
+            shoot(Shot {
+                velocity: Vec2::new(angle.cos(), angle.sin()) * speed,
+                homing: None,
+            })
+        };
+
+        let volley = self.volley;
+        let delay = match pattern {
+            BulletPattern::Single => {
+                fire(DOWN, 1.);
+                cooldown
+            }
+            BulletPattern::Aimed { speed } => {
+                fire(at_player, speed);
+                cooldown
+            }
+            BulletPattern::Spread {
+                count,
+                arc,
+                speed,
+                aimed,
+            } => {
+                let center = if aimed { at_player } else { DOWN };
+                for i in 0..count {
+                    let t = if count > 1 {
+                        i as f32 / (count - 1) as f32 - 0.5
+                    } else {
+                        0.
+                    };
+                    fire(center + t * arc, speed);
+                }
+                cooldown
+            }
+            BulletPattern::Spiral { arms, spin, speed } => {
+                for i in 0..arms {
+                    fire(
+                        DOWN + volley as f32 * spin + i as f32 * TAU / arms as f32,
+                        speed,
+                    );
+                }
+                cooldown
+            }
+            BulletPattern::Burst {
+                count,
+                gap,
+                speed,
+                aimed,
+            } => {
+                if volley.is_multiple_of(count) {
+                    let angle = if aimed { at_player } else { DOWN };
+                    self.aim = Vec2::new(angle.cos(), angle.sin());
+                }
+                fire(self.aim.y.atan2(self.aim.x), speed);
+                if (volley + 1).is_multiple_of(count) {
+                    cooldown
+                } else {
+                    gap
+                }
+            }
+            BulletPattern::Beam {
+                arc,
+                period,
+                gap,
+                speed,
+            } => {
+                let steps = (period / gap).round().max(1.) as u32;
+                let progress = (volley % steps) as f32 / steps as f32;
+                fire(DOWN + arc / 2. * (progress * TAU).sin(), speed);
+                if (volley + 1).is_multiple_of(steps) {
+                    cooldown
+                } else {
+                    gap
+                }
+            }
+            BulletPattern::Homing { speed, turn, fuel } => {
+                shoot(Shot {
+                    velocity: Vec2::new(0., -speed),
+                    homing: Some((turn, fuel)),
+                });
+                cooldown
+            }
+        };
+
+        self.volley += 1;
+        self.next_shot = sim_time.elapsed + delay;
+    }
+}
+
+/// Spawns a shot of a volley, fired from `origin`.
+pub fn spawn_shot(
+    commands: &mut Commands,
+    game_textures: &GameTextures,
+    sim_time: &SimTime,
+    origin: Vec2,
+    shot: Shot,
+    damage: f32,
+) {
+    let bullet = spawn_enemy_bullet(commands, game_textures, origin, shot.velocity, damage);
+    if let Some((turn, fuel)) = shot.homing {
+        commands
+            .entity(bullet)
+            .insert(Homing {
+                turn,
+                until: sim_time.elapsed + fuel,
+            })
+            .insert(Sprite {
+                color: MISSILE_COLOR,
+                ..Default::default()
+            });
+    }
+}
+
+/// Turns the missiles towards the player, as fast as they can turn, until they run out of fuel.
+pub fn homing_system(
+    sim_time: Res<SimTime>,
+    player_query: Query<&Transform, With<Player>>,
+    mut query: Query<(&Transform, &mut Velocity, &Homing), Without<Player>>,
+) {
+    let player = match player_query.get_single() {
+        Ok(transform) => transform.translation.xy(),
+        Err(_) => return,
+    };
+
+    for (transform, mut velocity, homing) in query.iter_mut() {
+        if sim_time.elapsed >= homing.until {
+            continue;
+        }
+
+        let current = Vec2::new(velocity.x, velocity.y);
+        let wanted = player - transform.translation.xy();
+        // `bullet_rotation_system` turns the sprite along
+        (velocity.x, velocity.y) = steer(current, wanted, homing.turn * TIME_STEP).into();
+    }
+}
+
+/// Turns the enemy bullets the way they fly, once their course changes.
+pub fn bullet_rotation_system(
+    mut query: Query<
+        (&Velocity, &mut Transform),
+        (With<Laser>, With<FromEnemy>, Changed<Velocity>),
+    >,
+) {
+    for (velocity, mut transform) in query.iter_mut() {
+        transform.rotation = bullet_rotation(Vec2::new(velocity.x, velocity.y));
+    }
+}
+
+/// Turns the `current` course towards the `wanted` one, by at most `max_turn` radians.
+pub fn steer(current: Vec2, wanted: Vec2, max_turn: f32) -> Vec2 {
+    // signed angle from the course to the wanted one
+    let turn = current.perp_dot(wanted).atan2(current.dot(wanted));
+    Mat2::from_angle(turn.clamp(-max_turn, max_turn)) * current
+}
+
+/// Rotation of a bullet sprite (pointing up) flying at `velocity`
+pub fn bullet_rotation(velocity: Vec2) -> Quat {
+    Quat::from_rotation_z(velocity.y.atan2(velocity.x) - PI / 2.)
+}
@@ -0,0 +1,229 @@
+use super::{formation::PathKind, EnemyKind};
+use crate::{boss::BossKind, SimTime};
+
+const WAVE_INTERMISSION: f64 = 3.; // seconds between the waves, while the banner shows
+const WAVE_TIME_BONUS: u32 = 2; // points per second a wave is cleared under its par time
+const WAVE_PERFECT_BONUS: u32 = 25; // points per wave number for clearing a wave without a hit
+const WAVE_DIFFICULTY_STEP: f32 = 0.08; // enemy speed and fire rate added by every wave
+const ENEMY_BULLETS_MAX: usize = 16; // enemy bullets on screen at once in the first wave
+const ENEMY_BULLETS_STEP: usize = 4; // enemy bullets on screen added by every wave
+
+/// Definition of a wave
+pub struct WaveDef {
+    /// enemies to spawn, in this order
+    pub roster: &'static [(EnemyKind, u32)],
+    /// enemies per formation
+    pub formation_size: u32,
+    /// paths the formations pick from
+    pub paths: &'static [PathKind],
+    /// seconds between two spawns
+    pub spawn_interval: f64,
+    /// seconds to clear the wave in (from its start) for a time bonus
+    pub par_time: f64,
+    /// fought once the roster is down
+    pub boss: Option<BossKind>,
+}
+
+/// The waves in order; once past the last one, it repeats at a higher difficulty.
+const WAVES: [WaveDef; 5] = [
+    WaveDef {
+        roster: &[(EnemyKind::Scout, 6)],
+        formation_size: 2,
+        paths: &[PathKind::Orbit],
+        spawn_interval: 1.,
+        par_time: 30.,
+        boss: None,
+    },
+    WaveDef {
+        roster: &[(EnemyKind::Scout, 6), (EnemyKind::Fighter, 2)],
+        formation_size: 2,
+        paths: &[PathKind::Orbit, PathKind::SineSweep],
+        spawn_interval: 1.,
+        par_time: 35.,
+        boss: None,
+    },
+    WaveDef {
+        roster: &[(EnemyKind::Fighter, 4), (EnemyKind::Scout, 4)],
+        formation_size: 3,
+        paths: &[PathKind::GridMarch, PathKind::FigureEight, PathKind::Swoop],
+        spawn_interval: 0.9,
+        par_time: 40.,
+        boss: Some(BossKind::Mothership),
+    },
+    WaveDef {
+        roster: &[
+            (EnemyKind::Scout, 4),
+            (EnemyKind::Fighter, 4),
+            (EnemyKind::Tank, 1),
+        ],
+        formation_size: 3,
+        paths: &[
+            PathKind::Orbit,
+            PathKind::Spline,
+            PathKind::Dive,
+            PathKind::GridMarch,
+        ],
+        spawn_interval: 0.8,
+        par_time: 45.,
+        boss: None,
+    },
+    WaveDef {
+        roster: &[
+            (EnemyKind::Fighter, 4),
+            (EnemyKind::Tank, 2),
+            (EnemyKind::Scout, 6),
+        ],
+        formation_size: 3,
+        paths: &[
+            PathKind::Orbit,
+            PathKind::GridMarch,
+            PathKind::SineSweep,
+            PathKind::FigureEight,
+            PathKind::Spline,
+            PathKind::Dive,
+            PathKind::Swoop,
+        ],
+        spawn_interval: 0.7,
+        par_time: 50.,
+        boss: Some(BossKind::Dreadnought),
+    },
+];
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum WavePhase {
+    /// banner time before the wave, until the sim time
+    Intermission { until: f64 },
+    /// spawning the roster, the next enemy at the sim time
+    Spawning { next_spawn: f64 },
+    /// everything spawned, waiting for the last enemy to go down
+    Fighting,
+}
+
+/// Bonus points for the last wave cleared
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct WaveBonus {
+    pub time: u32,
+    pub perfect: u32,
+}
+
+// region: --- Resources
+
+/// Resource - Wave in progress
+#[derive(Debug)]
+pub struct Wave {
+    pub number: u32, // from 1
+    pub phase: WavePhase,
+    pub spawned: u32,    // enemies of the roster spawned so far
+    pub started_at: f64, // sim time the spawning started
+    pub hit: bool,       // was the player hit during this wave?
+    pub boss_spawned: bool,
+    pub last_bonus: Option<WaveBonus>,
+}
+
+impl Default for Wave {
+    fn default() -> Self {
+        Self {
+            number: 1,
+            phase: WavePhase::Intermission {
+                until: WAVE_INTERMISSION,
+            },
+            spawned: 0,
+            started_at: 0.,
+            hit: false,
+            boss_spawned: false,
+            last_bonus: None,
+        }
+    }
+}
+
+impl Wave {
+    pub fn def(&self) -> &'static WaveDef {
+        let index = (self.number as usize - 1).min(WAVES.len() - 1);
+        &WAVES[index]
+    }
+
+    /// Boss at the end of the wave, the later waves repeat the last one without its boss
+    pub fn boss(&self) -> Option<BossKind> {
+        WAVES.get(self.number as usize - 1).and_then(|def| def.boss)
+    }
+
+    /// Multiplier of the enemy speed and fire rate
+    pub fn difficulty(&self) -> f32 {
+        1. + WAVE_DIFFICULTY_STEP * (self.number - 1) as f32
+    }
+
+    /// Enemy bullets on screen at once, past which the enemies hold their fire
+    pub fn bullet_cap(&self) -> usize {
+        ENEMY_BULLETS_MAX + ENEMY_BULLETS_STEP * (self.number as usize - 1)
+    }
+
+    pub fn in_intermission(&self) -> bool {
+        matches!(self.phase, WavePhase::Intermission { .. })
+    }
+
+    /// The next enemy of the roster, if any is left.
+    pub fn next_kind(&self) -> Option<EnemyKind> {
+        self.kind_at(self.spawned)
+    }
+
+    /// The enemy `index` of the roster.
+    pub fn kind_at(&self, mut index: u32) -> Option<EnemyKind> {
+        for &(kind, count) in self.def().roster {
+            if index < count {
+                return Some(kind);
+            }
+            index -= count;
+        }
+        None
+    }
+
+    /// Starts the wave once the intermission is over, with the first spawn `first_delay` seconds later.
+    pub fn update(&mut self, sim_time: &SimTime, first_delay: f64) {
+        if let WavePhase::Intermission { until } = self.phase {
+            if sim_time.elapsed >= until {
+                self.phase = WavePhase::Spawning {
+                    next_spawn: sim_time.elapsed + first_delay,
+                };
+                self.started_at = sim_time.elapsed;
+            }
+        }
+    }
+
+    /// Counts the enemy as spawned, and schedules the next one `next_delay` seconds later,
+    /// `None` if it was the last one.
+    pub fn spawned(&mut self, sim_time: &SimTime, next_delay: Option<f64>) {
+        self.spawned += 1;
+        self.phase = match next_delay {
+            Some(delay) => WavePhase::Spawning {
+                next_spawn: sim_time.elapsed + delay,
+            },
+            None => WavePhase::Fighting,
+        };
+    }
+
+    /// Ends the wave, starts the intermission before the next one, and returns the bonus earned
+    /// for clearing it within `par_time` seconds.
+    pub fn clear(&mut self, sim_time: &SimTime, par_time: f64) -> WaveBonus {
+        let duration = sim_time.elapsed - self.started_at;
+        let bonus = WaveBonus {
+            time: (par_time - duration).max(0.) as u32 * WAVE_TIME_BONUS,
+            perfect: if self.hit {
+                0
+            } else {
+                WAVE_PERFECT_BONUS * self.number
+            },
+        };
+
+        *self = Self {
+            number: self.number + 1,
+            phase: WavePhase::Intermission {
+                until: sim_time.elapsed + WAVE_INTERMISSION,
+            },
+            last_bonus: Some(bonus),
+            ..Default::default()
+        };
+        bonus
+    }
+}
+
+// endregion: --- Resources
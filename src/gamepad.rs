@@ -0,0 +1,102 @@
+use bevy::prelude::*;
+
+use crate::AppState;
+
+pub const STICK_STEPS: f32 = 100.; // steps of the stick from the center to a side, so replays keep it exactly
+
+// region: --- Resources
+
+/// Resource - Gamepad the player plays with: the first one connected, if any
+#[derive(Default)]
+pub struct PlayerGamepad(pub Option<Gamepad>);
+
+impl PlayerGamepad {
+    /// Position of the left stick, from (-1, -1) to (1, 1); (0, 0) without a gamepad.
+    pub fn stick(&self, axes: &Axis<GamepadAxis>) -> Vec2 {
+        let gamepad = match self.0 {
+            Some(gamepad) => gamepad,
+            None => return Vec2::ZERO,
+        };
+
+        let axis = |axis_type| axes.get(GamepadAxis(gamepad, axis_type)).unwrap_or(0.);
+        Vec2::new(
+            axis(GamepadAxisType::LeftStickX),
+            axis(GamepadAxisType::LeftStickY),
+        )
+    }
+}
+
+// endregion: --- Resources
+
+/// The stick past the `dead_zone`, rescaled so it goes from 0 at the dead zone to 1 at full tilt,
+/// in steps of `1 / STICK_STEPS`.
+pub fn stick_input(stick: Vec2, dead_zone: f32) -> Vec2 {
+    let tilt = stick.length();
+    if tilt <= dead_zone {
+        return Vec2::ZERO;
+    }
+    let rescaled = stick * ((tilt - dead_zone) / (1. - dead_zone)).min(1.) / tilt;
+    (rescaled * STICK_STEPS).round() / STICK_STEPS
+}
+
+/// Keeps track of the player's gamepad as controllers come and go.
+pub struct GamepadPlugin;
+
+impl Plugin for GamepadPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(PlayerGamepad::default())
+            .add_system(gamepad_connection_system);
+    }
+}
+
+/// Hands the player a pad when one connects, and pauses the game when it disconnects.
+fn gamepad_connection_system(
+    mut events: EventReader<GamepadEvent>,
+    gamepads: Res<Gamepads>,
+    mut player_gamepad: ResMut<PlayerGamepad>,
+    mut app_state: ResMut<State<AppState>>,
+) {
+    for GamepadEvent(gamepad, event_type) in events.iter() {
+        match event_type {
+            GamepadEventType::Connected if player_gamepad.0.is_none() => {
+                player_gamepad.0 = Some(*gamepad);
+            }
+            GamepadEventType::Disconnected if player_gamepad.0 == Some(*gamepad) => {
+                // carry on with another pad, if there is one
+                player_gamepad.0 = gamepads.iter().copied().find(|other| other != gamepad);
+
+                if *app_state.current() == AppState::InGame
+                    && app_state.push(AppState::Paused).is_ok()
+                {
+                    println!("paused");
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_stick_starts_past_the_dead_zone() {
+        assert_eq!(stick_input(Vec2::new(0.2, -0.1), 0.25), Vec2::ZERO);
+        assert_eq!(stick_input(Vec2::new(1., 0.), 0.25), Vec2::new(1., 0.));
+        // halfway between the dead zone and full tilt moves at half speed
+        assert_eq!(
+            stick_input(Vec2::new(0., -0.625), 0.25),
+            Vec2::new(0., -0.5)
+        );
+        assert_eq!(stick_input(Vec2::new(0.3, 0.), 0.), Vec2::new(0.3, 0.));
+        // the corners of a square stick are no faster than full tilt
+        assert!((stick_input(Vec2::new(1., 1.), 0.25).length() - 1.).abs() < 0.01);
+    }
+
+    #[test]
+    fn the_stick_moves_in_steps() {
+        let stick = stick_input(Vec2::new(0.123456, 0.654321), 0.);
+        assert_eq!(stick * STICK_STEPS, (stick * STICK_STEPS).round());
+    }
+}
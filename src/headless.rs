@@ -0,0 +1,99 @@
+use anyhow::Context;
+use bevy::{
+    app::AppExit,
+    ecs::event::{Events, ManualEventReader},
+    input::InputPlugin,
+    prelude::*,
+};
+use bevy_kira_audio::{Audio, AudioChannel};
+
+use crate::{
+    bindings::Bindings,
+    boss::BossPlugin,
+    bunker::BunkerPlugin,
+    config::GameConfig,
+    enemy::{EnemyPlugin, Level, LevelRunner},
+    gamepad::GamepadPlugin,
+    player::PlayerPlugin,
+    powerup::PowerUpPlugin,
+    replay::{self, Replay, ReplayPlayer, ReplayPlugin, ReplayRecorder},
+    ufo::{UfoChannel, UfoPlugin},
+    GameFonts, GamePlugin, GameSounds, GameState, GameTextures, WinSize,
+};
+
+/// Runs a whole match without a window, GPU or audio device and returns the final game state,
+/// or the error of a level that does not load.
+///
+/// Every `App::update` advances the simulation by exactly one `TIME_STEP`, so the match
+/// runs as fast as the CPU allows instead of in real time.
+/// The match lasts `config.ticks`, or until the `replay` runs out.
+pub fn run(config: GameConfig, replay: Option<Replay>) -> anyhow::Result<GameState> {
+    let ticks = config.ticks;
+    let level_runner = match &config.level {
+        Some(path) => LevelRunner::loaded(
+            Level::load(path).with_context(|| format!("could not load level {}", path))?,
+        ),
+        None => LevelRunner::default(),
+    };
+    let mut app = App::new();
+
+    if let Some(replay) = replay {
+        app.insert_resource(ReplayPlayer::new(replay));
+    }
+
+    app.insert_resource(WinSize {
+        width: config.width,
+        height: config.height,
+    })
+    .insert_resource(config)
+    .insert_resource(level_runner)
+    // nothing is rendered or played, so the asset handles can stay empty
+    .insert_resource(GameTextures::default())
+    .insert_resource(GameSounds::default())
+    .insert_resource(GameFonts::default())
+    .insert_resource(Audio::default())
+    .insert_resource(AudioChannel::<UfoChannel>::default())
+    .insert_resource(Bindings::default())
+    .add_plugins(MinimalPlugins)
+    // no keyboard or gamepad sends any input, so the player just stands still
+    .add_plugin(InputPlugin)
+    .add_plugin(GamePlugin)
+    .add_plugin(GamepadPlugin)
+    .add_plugin(PlayerPlugin)
+    .add_plugin(EnemyPlugin)
+    .add_plugin(BossPlugin)
+    .add_plugin(BunkerPlugin)
+    .add_plugin(UfoPlugin)
+    .add_plugin(PowerUpPlugin)
+    .add_plugin(ReplayPlugin)
+    .add_system_to_stage(CoreStage::Last, audio_drain_system);
+
+    let mut exit_reader = ManualEventReader::<AppExit>::default();
+    for _ in 0..ticks {
+        app.update();
+
+        let exit_events = app.world.resource::<Events<AppExit>>();
+        if exit_reader.iter(exit_events).next().is_some() {
+            break;
+        }
+    }
+
+    if let Some(recorder) = app.world.get_resource::<ReplayRecorder>() {
+        replay::save_recording(
+            recorder,
+            app.world.resource::<GameState>(),
+            app.world.resource::<GameConfig>(),
+        );
+    }
+
+    Ok(app
+        .world
+        .remove_resource::<GameState>()
+        .expect("GameState is inserted by the PlayerPlugin"))
+}
+
+/// There is no `AudioPlugin` to consume the queued play commands, so drop them every frame.
+fn audio_drain_system(mut audio: ResMut<Audio>, mut ufo_channel: ResMut<AudioChannel<UfoChannel>>) {
+    *audio = Audio::default();
+    *ufo_channel = AudioChannel::default();
+}
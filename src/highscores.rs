@@ -0,0 +1,51 @@
+use std::{fs, io};
+
+const HIGH_SCORES_FILE: &str = "highscores.txt";
+const HIGH_SCORES_MAX: usize = 10;
+
+/// Resource - Best scores, highest first, kept in `highscores.txt` (one score per line)
+#[derive(Default)]
+pub struct HighScores(pub Vec<u32>);
+
+impl HighScores {
+    /// Loads the saved scores; a missing or unreadable file is an empty table.
+    pub fn load() -> Self {
+        let scores = fs::read_to_string(HIGH_SCORES_FILE)
+            .map(|text| {
+                text.lines()
+                    .filter_map(|line| line.trim().parse().ok())
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        let mut high_scores = HighScores(scores);
+        high_scores.0.sort_unstable_by(|a, b| b.cmp(a));
+        high_scores.0.truncate(HIGH_SCORES_MAX);
+        high_scores
+    }
+
+    pub fn save(&self) -> io::Result<()> {
+        let text: String = self.0.iter().map(|score| format!("{}\n", score)).collect();
+        fs::write(HIGH_SCORES_FILE, text)
+    }
+
+    /// Adds the score if it makes the table, returns whether it did.
+    pub fn insert(&mut self, score: u32) -> bool {
+        if score == 0 {
+            return false;
+        }
+
+        let rank = self
+            .0
+            .iter()
+            .position(|&high| score > high)
+            .unwrap_or(self.0.len());
+        if rank >= HIGH_SCORES_MAX {
+            return false;
+        }
+
+        self.0.insert(rank, score);
+        self.0.truncate(HIGH_SCORES_MAX);
+        true
+    }
+}
@@ -0,0 +1,468 @@
+use bevy::prelude::*;
+
+use crate::{
+    boss::Boss,
+    components::{
+        Attributes, HealthText, LivesText, Player, PowerUpText, ScoreText, WaveText, WeaponText,
+    },
+    enemy::Wave,
+    game_in_stack,
+    powerup::PowerUpTimers,
+    weapon::Weapon,
+    AppState, GameFonts, GameState, PlayerState, SimTime,
+};
+
+const BAR_SIZE: (f32, f32) = (120., 8.);
+const LOW_HEALTH: f32 = 0.3; // fraction of the max health the health bar turns red at
+const HIT_FLASH: f32 = 0.15; // seconds the health bar flashes for after a hit
+const BOSS_BAR_SIZE: (f32, f32) = (300., 10.);
+const BOSS_BAR_TOP: f32 = 100.;
+const POWER_UP_TEXT_COLOR: Color = Color::rgb(0.5, 0.9, 1.);
+
+// region: --- Components
+
+/// Root of the in-game HUD, spawned when a game starts and despawned when it ends
+#[derive(Component)]
+struct HudUi;
+
+/// Fill of the health bar
+#[derive(Component, Default)]
+struct HealthBar {
+    fraction: f32, // of the max health, to tell a hit from a respawn
+    flash: f32,    // seconds left to flash
+}
+
+/// Fill of the weapon reload bar
+#[derive(Component)]
+struct ReloadBar;
+
+/// "Wave N" banner in the middle of the screen, shown between the waves
+#[derive(Component)]
+struct WaveBanner;
+
+/// Boss health bar, shown while a boss fights
+#[derive(Component)]
+struct BossBar;
+
+/// Fill of the boss health bar
+#[derive(Component)]
+struct BossBarFill;
+
+// endregion: --- Components
+
+/// Health, score, lives, weapon, weapon reload, power-ups and wave of the current game, and the health of the boss.
+/// The texts are only rewritten when the value behind them changes.
+pub struct HudPlugin;
+
+impl Plugin for HudPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system_set(SystemSet::on_enter(AppState::InGame).with_system(hud_setup_system))
+            .add_system_set(SystemSet::on_exit(AppState::InGame).with_system(hud_cleanup_system))
+            // keep updating under the pause and game over screens
+            .add_system_set(
+                SystemSet::new()
+                    .with_run_criteria(game_in_stack)
+                    .with_system(health_text_update_system)
+                    .with_system(score_text_update_system)
+                    .with_system(lives_text_update_system)
+                    .with_system(health_bar_update_system)
+                    .with_system(reload_bar_update_system)
+                    .with_system(weapon_text_update_system)
+                    .with_system(power_up_text_update_system)
+                    .with_system(wave_text_update_system)
+                    .with_system(wave_banner_update_system)
+                    .with_system(boss_bar_update_system),
+            );
+    }
+}
+
+fn hud_setup_system(mut commands: Commands, game_fonts: Res<GameFonts>) {
+    // the texts start empty, the update systems fill them in once they are added
+    let text_bundle = |top: f32, left: Option<f32>, color: Color| TextBundle {
+        style: Style {
+            position_type: PositionType::Absolute,
+            position: Rect {
+                top: Val::Px(top),
+                left: left.map_or(Val::Undefined, Val::Px),
+                right: left.map_or(Val::Px(15.), |_| Val::Undefined),
+                ..default()
+            },
+            ..default()
+        },
+        text: Text::with_section(
+            "",
+            TextStyle {
+                font: game_fonts.hud.clone(),
+                font_size: 16.,
+                color,
+            },
+            Default::default(),
+        ),
+        ..default()
+    };
+
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                size: Size::new(Val::Percent(100.), Val::Percent(100.)),
+                ..default()
+            },
+            color: Color::NONE.into(),
+            ..default()
+        })
+        .insert(HudUi)
+        .with_children(|parent| {
+            // health and lives top left, score top right
+            parent
+                .spawn_bundle(text_bundle(15., Some(15.), Color::GREEN))
+                .insert(HealthText);
+            parent
+                .spawn_bundle(text_bundle(40., Some(15.), Color::GREEN))
+                .insert(LivesText);
+            parent
+                .spawn_bundle(text_bundle(15., None, Color::BLUE))
+                .insert(ScoreText);
+            parent
+                .spawn_bundle(text_bundle(40., None, Color::BLUE))
+                .insert(WaveText);
+            parent
+                .spawn_bundle(text_bundle(65., None, Color::ORANGE))
+                .insert(WeaponText);
+            // and the bars below them
+            spawn_bar(parent, 65., HealthBar::default());
+            spawn_bar(parent, 80., ReloadBar);
+            // and the power-ups that wear off below them, clear of the boss bar
+            parent
+                .spawn_bundle(text_bundle(
+                    BOSS_BAR_TOP + 15.,
+                    Some(15.),
+                    POWER_UP_TEXT_COLOR,
+                ))
+                .insert(PowerUpText);
+        });
+
+    // the banner gets its own centered root, so it does not push the texts around
+    let banner_style = |font_size: f32, color: Color| TextStyle {
+        font: game_fonts.hud.clone(),
+        font_size,
+        color,
+    };
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                size: Size::new(Val::Percent(100.), Val::Percent(100.)),
+                position_type: PositionType::Absolute,
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..default()
+            },
+            color: Color::NONE.into(),
+            ..default()
+        })
+        .insert(HudUi)
+        .with_children(|parent| {
+            parent
+                .spawn_bundle(TextBundle {
+                    text: Text {
+                        // the wave, then the bonus of the wave before
+                        sections: vec![
+                            TextSection {
+                                value: String::new(),
+                                style: banner_style(32., Color::GREEN),
+                            },
+                            TextSection {
+                                value: String::new(),
+                                style: banner_style(16., Color::YELLOW),
+                            },
+                        ],
+                        alignment: TextAlignment {
+                            horizontal: HorizontalAlign::Center,
+                            ..default()
+                        },
+                    },
+                    ..default()
+                })
+                .insert(WaveBanner);
+        });
+
+    // the boss bar, centered below the texts, hidden until a boss comes in
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                size: Size::new(Val::Percent(100.), Val::Px(BOSS_BAR_SIZE.1)),
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    top: Val::Px(BOSS_BAR_TOP),
+                    ..default()
+                },
+                justify_content: JustifyContent::Center,
+                display: Display::None,
+                ..default()
+            },
+            color: Color::NONE.into(),
+            ..default()
+        })
+        .insert(HudUi)
+        .insert(BossBar)
+        .with_children(|parent| {
+            parent
+                .spawn_bundle(NodeBundle {
+                    style: Style {
+                        size: Size::new(Val::Px(BOSS_BAR_SIZE.0), Val::Percent(100.)),
+                        ..default()
+                    },
+                    color: Color::rgb(0.2, 0.2, 0.2).into(),
+                    ..default()
+                })
+                .with_children(|bar| {
+                    bar.spawn_bundle(NodeBundle {
+                        style: Style {
+                            size: Size::new(Val::Percent(100.), Val::Percent(100.)),
+                            ..default()
+                        },
+                        color: Color::PURPLE.into(),
+                        ..default()
+                    })
+                    .insert(BossBarFill);
+                });
+        });
+}
+
+/// Spawns an empty bar, the `fill` marks the node whose width shows the value.
+fn spawn_bar(parent: &mut ChildBuilder, top: f32, fill: impl Component) {
+    parent
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    top: Val::Px(top),
+                    left: Val::Px(15.),
+                    ..default()
+                },
+                size: Size::new(Val::Px(BAR_SIZE.0), Val::Px(BAR_SIZE.1)),
+                ..default()
+            },
+            color: Color::rgb(0.2, 0.2, 0.2).into(),
+            ..default()
+        })
+        .with_children(|bar| {
+            bar.spawn_bundle(NodeBundle {
+                style: Style {
+                    size: Size::new(Val::Percent(0.), Val::Percent(100.)),
+                    ..default()
+                },
+                ..default()
+            })
+            .insert(fill);
+        });
+}
+
+fn hud_cleanup_system(mut commands: Commands, query: Query<Entity, With<HudUi>>) {
+    for entity in query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+/// Shows the health of the current player, and keeps the last value while waiting for a respawn.
+fn health_text_update_system(
+    player_query: Query<&Attributes, (With<Player>, Changed<Attributes>)>,
+    mut query: Query<&mut Text, With<HealthText>>,
+    added_query: Query<(), Added<HealthText>>,
+) {
+    // a new text starts out with the default health the next player spawns with
+    let health = match player_query.get_single() {
+        Ok(attributes) => attributes.health,
+        Err(_) if !added_query.is_empty() => Attributes::default().health,
+        Err(_) => return,
+    };
+
+    for mut text in query.iter_mut() {
+        text.sections[0].value = format!("Health: {}", health);
+    }
+}
+
+fn score_text_update_system(
+    game_state: Res<GameState>,
+    mut query: Query<&mut Text, With<ScoreText>>,
+    added_query: Query<(), Added<ScoreText>>,
+) {
+    if !game_state.is_changed() && added_query.is_empty() {
+        return;
+    }
+
+    for mut text in query.iter_mut() {
+        text.sections[0].value = format!("Score: {}", game_state.score);
+    }
+}
+
+fn lives_text_update_system(
+    player_state: Res<PlayerState>,
+    mut query: Query<&mut Text, With<LivesText>>,
+    added_query: Query<(), Added<LivesText>>,
+) {
+    if !player_state.is_changed() && added_query.is_empty() {
+        return;
+    }
+
+    for mut text in query.iter_mut() {
+        text.sections[0].value = format!("Lives: {}", player_state.lives);
+    }
+}
+
+/// Sizes the health bar to the player's health, red when low, white for a moment after a hit.
+fn health_bar_update_system(
+    time: Res<Time>,
+    player_query: Query<&Attributes, (With<Player>, Changed<Attributes>)>,
+    mut query: Query<(&mut HealthBar, &mut Style, &mut UiColor)>,
+    added_query: Query<(), Added<HealthBar>>,
+) {
+    let fraction = match player_query.get_single() {
+        Ok(attributes) => Some((attributes.health / attributes.max_health).clamp(0., 1.)),
+        // full until the first player spawns
+        Err(_) if !added_query.is_empty() => Some(1.),
+        Err(_) => None,
+    };
+
+    for (mut bar, mut style, mut color) in query.iter_mut() {
+        if let Some(fraction) = fraction {
+            if fraction < bar.fraction {
+                bar.flash = HIT_FLASH;
+            }
+            bar.fraction = fraction;
+            style.size.width = Val::Percent(fraction * 100.);
+        }
+
+        bar.flash = (bar.flash - time.delta_seconds()).max(0.);
+        color.0 = if bar.flash > 0. {
+            Color::WHITE
+        } else if bar.fraction <= LOW_HEALTH {
+            Color::RED
+        } else {
+            Color::GREEN
+        };
+    }
+}
+
+/// Fills the reload bar as the weapon reloads, orange until it can fire again.
+fn reload_bar_update_system(
+    sim_time: Res<SimTime>,
+    player_query: Query<&Weapon, With<Player>>,
+    mut query: Query<(&mut Style, &mut UiColor), With<ReloadBar>>,
+) {
+    if let Ok(weapon) = player_query.get_single() {
+        let reloaded = weapon.reloaded(sim_time.elapsed);
+        for (mut style, mut color) in query.iter_mut() {
+            style.size.width = Val::Percent(reloaded * 100.);
+            color.0 = if reloaded < 1. {
+                Color::ORANGE
+            } else {
+                Color::WHITE
+            };
+        }
+    }
+}
+
+/// Names the weapon of the current player, and keeps the last one while waiting for a respawn.
+fn weapon_text_update_system(
+    player_query: Query<&Weapon, (With<Player>, Changed<Weapon>)>,
+    mut query: Query<&mut Text, With<WeaponText>>,
+) {
+    if let Ok(weapon) = player_query.get_single() {
+        for mut text in query.iter_mut() {
+            text.sections[0].value = format!("Weapon: {}", weapon.kind.def().name);
+        }
+    }
+}
+
+/// Lists the power-ups that wear off, with their seconds left.
+fn power_up_text_update_system(
+    power_up_timers: Res<PowerUpTimers>,
+    sim_time: Res<SimTime>,
+    mut query: Query<&mut Text, With<PowerUpText>>,
+) {
+    let value = power_up_timers
+        .remaining(sim_time.elapsed)
+        .map(|(kind, left)| format!("{} {}", kind.def().name, left.ceil()))
+        .collect::<Vec<_>>()
+        .join("  ");
+
+    for mut text in query.iter_mut() {
+        // the seconds only tick over once a second, do not rewrite the text every frame
+        if text.sections[0].value != value {
+            text.sections[0].value = value.clone();
+        }
+    }
+}
+
+fn wave_text_update_system(
+    wave: Res<Wave>,
+    mut query: Query<&mut Text, With<WaveText>>,
+    added_query: Query<(), Added<WaveText>>,
+) {
+    if !wave.is_changed() && added_query.is_empty() {
+        return;
+    }
+
+    for mut text in query.iter_mut() {
+        text.sections[0].value = format!("Wave: {}", wave.number);
+    }
+}
+
+/// Shows the banner during the intermission before every wave, empty otherwise.
+fn wave_banner_update_system(
+    wave: Res<Wave>,
+    mut query: Query<&mut Text, With<WaveBanner>>,
+    added_query: Query<(), Added<WaveBanner>>,
+) {
+    if !wave.is_changed() && added_query.is_empty() {
+        return;
+    }
+
+    let (title, bonus) = match (wave.in_intermission(), wave.last_bonus) {
+        (false, _) => (String::new(), String::new()),
+        (true, None) => (format!("WAVE {}", wave.number), String::new()),
+        (true, Some(bonus)) => (
+            format!("WAVE {}", wave.number),
+            format!(
+                "\nTime bonus: {}\nNo hit bonus: {}",
+                bonus.time, bonus.perfect
+            ),
+        ),
+    };
+
+    for mut text in query.iter_mut() {
+        text.sections[0].value = title.clone();
+        text.sections[1].value = bonus.clone();
+    }
+}
+
+/// Shows the boss bar while a boss fights, sized to its health.
+fn boss_bar_update_system(
+    boss_query: Query<&Attributes, With<Boss>>,
+    mut bar_query: Query<&mut Style, (With<BossBar>, Without<BossBarFill>)>,
+    mut fill_query: Query<&mut Style, (With<BossBarFill>, Without<BossBar>)>,
+) {
+    // the boss leaves no trace once down, so look for it every frame
+    let fraction = boss_query
+        .get_single()
+        .ok()
+        .map(|attributes| (attributes.health / attributes.max_health).clamp(0., 1.));
+    let display = if fraction.is_some() {
+        Display::Flex
+    } else {
+        Display::None
+    };
+
+    for mut style in bar_query.iter_mut() {
+        if style.display != display {
+            style.display = display;
+        }
+    }
+    if let Some(fraction) = fraction {
+        for mut style in fill_query.iter_mut() {
+            if style.size.width != Val::Percent(fraction * 100.) {
+                style.size.width = Val::Percent(fraction * 100.);
+            }
+        }
+    }
+}
@@ -1,24 +1,59 @@
 #![allow(unused)]
-mod player;
+#![allow(clippy::too_many_arguments, clippy::type_complexity)]
+mod bindings;
+mod boss;
+mod bunker;
 mod components;
+mod config;
 mod enemy;
-
-use bevy::{prelude::*, math::Vec3Swizzles, sprite::collide_aabb::collide, ecs::{system::Insert, schedule::ShouldRun}, utils::HashSet, core::FixedTimestep};
+mod gamepad;
+mod headless;
+mod highscores;
+mod hud;
+mod menu;
+mod player;
+mod powerup;
+mod replay;
+mod ufo;
+mod weapon;
+
+use bevy::{
+    app::AppExit,
+    core::{FixedTimestep, FixedTimesteps},
+    ecs::{schedule::ShouldRun, system::Insert},
+    math::Vec3Swizzles,
+    prelude::*,
+    sprite::collide_aabb::collide,
+    transform::TransformSystem,
+    utils::{HashMap, HashSet},
+    window::WindowFocused,
+};
 use bevy_kira_audio::{Audio, AudioPlugin, AudioSource};
-use components::{Velocity, Player, Movable, SpriteSize, Laser, FromPlayer, Enemy, ExplosionToSpawn, Explosion, ExplosionTimer, FromEnemy, Attributes, HealthText};
-use enemy::EnemyPlugin;
+use bindings::{Action, Bindings};
+use boss::{BossKind, BossPlugin};
+use bunker::BunkerPlugin;
+use components::{
+    Armor, Attributes, Damage, Enemy, Explosion, ExplosionTimer, ExplosionToSpawn, FromEnemy,
+    FromPlayer, Interpolated, Laser, Movable, Player, SpriteSize, Velocity,
+};
+use config::GameConfig;
+use enemy::{EnemyKind, EnemyPlugin, Wave};
+use gamepad::{GamepadPlugin, PlayerGamepad};
+use hud::HudPlugin;
+use menu::MenuPlugin;
 use player::*;
+use powerup::{DropRoll, PowerUpPlugin, PowerUpTimers};
+use rand::{rngs::StdRng, SeedableRng};
+use replay::{Replay, ReplayPlayer, ReplayPlugin};
+use std::time::Duration;
+use ufo::UfoPlugin;
+use weapon::{Piercing, WeaponKind};
 
 // region: --- Asset Constants
 
 const PLAYER_SPRITE: &str = "player_b_01.png";
 const PLAYER_SIZE: (f32, f32) = (144., 75.);
-const PLAYER_LASER_SPRITE: &str = "laser_a_01.png";
-const PLAYER_LASER_SIZE: (f32, f32) = (9., 54.);
-const PLAYER_LASER_SOUND: &str = "sounds/player_laser.wav";
 
-const ENEMY_SPRITE: &str = "enemy_a_01.png";
-const ENEMY_SIZE: (f32, f32) = (144., 75.);
 const ENEMY_LASER_SPRITE: &str = "laser_b_01.png";
 const ENEMY_LASER_SIZE: (f32, f32) = (17., 55.);
 const ENEMY_LASER_SOUND: &str = "sounds/enemy_laser.wav";
@@ -28,6 +63,14 @@ const ENEMY_EXPLOSION_SOUND: &str = "sounds/explosion.wav";
 const PLAYER_EXPLOSION_SOUND: &str = "sounds/player_explosion.wav";
 const PLAYER_HIT_SOUND: &str = "sounds/player_hit.ogg";
 
+const UFO_SPRITE: &str = "enemy_a_01.png";
+const UFO_SIZE: (f32, f32) = (144., 75.);
+const UFO_SOUND: &str = "sounds/ufo.wav";
+
+const POWER_UP_SOUND: &str = "sounds/powerup.wav";
+
+const HUD_FONT: &str = "fonts/AgentOrange.ttf";
+
 const SPRITE_SCALE: f32 = 0.5;
 
 const EXPLOSION_LENGTH: usize = 16;
@@ -36,12 +79,16 @@ const EXPLOSION_LENGTH: usize = 16;
 
 // region: --- Game Contants
 
+const WINDOW_WIDTH: f32 = 598.;
+const WINDOW_HEIGHT: f32 = 676.;
+
 const TIME_STEP: f32 = 1. / 60.;
 const BASE_SPEED: f32 = 500.;
 
 const PLAYER_RESPAWN_DELAY: f64 = 2.;
+const PLAYER_LIVES: u32 = 3;
+const PLAYER_MOVE_BAND: f32 = 0.35; // default of `--move-band`
 const MAX_ENEMY_COUNT: u32 = 10;
-const FORMATION_MEMBERS_MAX: u32 = 2;
 
 // endregion: --- Game Contants
 
@@ -52,34 +99,90 @@ pub struct WinSize {
     pub height: f32,
 }
 
+#[derive(Default)]
 struct GameTextures {
     player: Handle<Image>,
-    player_laser: Handle<Image>,
-    enemy: Handle<Image>,
+    weapons: HashMap<WeaponKind, Handle<Image>>,
+    enemies: HashMap<EnemyKind, Handle<Image>>,
+    bosses: HashMap<BossKind, Handle<Image>>,
     enemy_laser: Handle<Image>,
+    ufo: Handle<Image>,
     explosion: Handle<TextureAtlas>,
 }
 
+#[derive(Default)]
 struct GameSounds {
-    player_laser: Handle<AudioSource>,
+    weapons: HashMap<WeaponKind, Handle<AudioSource>>,
     enemy_laser: Handle<AudioSource>,
     enemy_explosion: Handle<AudioSource>,
     player_explosion: Handle<AudioSource>,
     player_hit: Handle<AudioSource>,
+    ufo: Handle<AudioSource>,
+    power_up: Handle<AudioSource>,
+}
+
+#[derive(Default)]
+struct GameFonts {
+    hud: Handle<Font>,
 }
 
 struct EnemyCount(u32);
 
+/// Resource - Simulation clock, advanced by exactly one `TIME_STEP` per simulation tick.
+/// Gameplay timers read this instead of `Time` so they do not depend on the frame rate.
+#[derive(Default)]
+pub struct SimTime {
+    pub tick: u64,
+    pub elapsed: f64,
+}
+
+impl SimTime {
+    pub fn advance(&mut self) {
+        self.tick += 1;
+        self.elapsed = self.tick as f64 * TIME_STEP as f64;
+    }
+}
+
+/// Resource - Player input for the next simulation tick.
+/// Presses are latched here once per frame so a tick never misses or repeats them.
+#[derive(Default)]
+pub struct PlayerInput {
+    pub left: bool,
+    pub right: bool,
+    pub up: bool,
+    pub down: bool,
+    pub stick: Vec2, // analog move, past the dead zone, see `gamepad::stick_input`
+    pub fire: bool,
+    pub fire_held: bool, // autofire
+    pub switch_weapon: bool,
+}
+
+/// Resource - Game RNG, the only source of randomness the simulation may draw from.
+/// Same seed + same input = same enemy paths, shots and score.
+pub struct GameRng(pub StdRng);
+
+impl GameRng {
+    pub fn from_seed(seed: u64) -> Self {
+        Self(StdRng::seed_from_u64(seed))
+    }
+}
+
 #[derive(Debug, Clone, Eq, PartialEq, Hash)]
 enum AppState {
     MainMenu,
+    Settings,
+    Controls,
+    HighScores,
     InGame,
     Paused,
+    GameOver, // pushed on top of InGame, so the last moments of the game stay on screen
 }
 
 struct PlayerState {
-    on: bool, // is the player alive?
+    on: bool,       // is the player alive?
     last_shot: f64, // -1 if not shot
+    lives: u32,     // lives left, including the current one
+    shots: u32,     // fired this game, the UFO bonus depends on it
 }
 
 impl Default for PlayerState {
@@ -87,22 +190,47 @@ impl Default for PlayerState {
         Self {
             on: false,
             last_shot: -1.,
+            lives: PLAYER_LIVES,
+            shots: 0,
         }
     }
 }
 
-pub struct GameState {
-    pub score: u32,
+/// Resource - Game mode, picked in the main menu or with `--mode`
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum GameMode {
+    #[default]
+    Waves, // waves of enemy types flying formations, or a scripted level
+    Classic, // the original arcade game: one grid of invaders marching down
 }
 
-impl Default for GameState {
-    fn default() -> Self {
-        GameState {
-            score: 0,
+impl GameMode {
+    pub const ALL: [GameMode; 2] = [GameMode::Waves, GameMode::Classic];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            GameMode::Waves => "waves",
+            GameMode::Classic => "classic",
         }
     }
 }
 
+impl std::str::FromStr for GameMode {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        GameMode::ALL
+            .into_iter()
+            .find(|mode| mode.name() == name)
+            .ok_or_else(|| format!("unknown game mode `{}`", name))
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct GameState {
+    pub score: u32,
+}
+
 impl PlayerState {
     pub fn is_alive(&self) -> bool {
         self.on
@@ -111,6 +239,7 @@ impl PlayerState {
     pub fn shot(&mut self, time: f64) {
         self.on = false;
         self.last_shot = time;
+        self.lives = self.lives.saturating_sub(1);
     }
 
     pub fn spawned(&mut self) {
@@ -118,38 +247,208 @@ impl PlayerState {
         self.last_shot = -1.;
     }
 }
-    
+
 // endregion: --- Resources
 
+/// Stage - Gameplay simulation.
+/// Runs once per `TIME_STEP` of real time in a window, and once per update when headless.
+/// Single threaded, and every system is placed in a `SimLabel` phase, so they always run
+/// (and draw from the `GameRng`) in the same order.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, StageLabel)]
+pub struct SimulationStage;
+
+const SIMULATION_TIMESTEP: &str = "simulation";
+
+/// Phases of one simulation step, in order
+#[derive(Debug, Clone, PartialEq, Eq, Hash, SystemLabel)]
+pub enum SimLabel {
+    Clock,
+    Spawn,
+    Input,
+    Fire,
+    Movement,
+    Collision,
+    Effects,
+    Snapshot,
+}
+
 fn main() {
-    App::new()
-        .insert_resource(ClearColor(Color::rgb(0.04, 0.04, 0.04)))
+    let mut config = match GameConfig::from_args() {
+        Ok(config) => config,
+        Err(err) => {
+            eprintln!("error: {}", err);
+            std::process::exit(2);
+        }
+    };
+
+    let replay = match config.replay.as_ref().map(Replay::load).transpose() {
+        Ok(replay) => replay,
+        Err(err) => {
+            eprintln!("error: could not load replay: {}", err);
+            std::process::exit(2);
+        }
+    };
+    if let Some(replay) = &replay {
+        replay.configure(&mut config);
+    }
+
+    if config.headless {
+        let expected_score = replay.as_ref().and_then(|replay| replay.score);
+        let game_state = match headless::run(config, replay) {
+            Ok(game_state) => game_state,
+            Err(err) => {
+                eprintln!("error: {:#}", err);
+                std::process::exit(2);
+            }
+        };
+        println!("{:?}", game_state);
+
+        // replays double as regression tests
+        if let Some(expected_score) = expected_score {
+            if game_state.score != expected_score {
+                eprintln!(
+                    "error: replay recorded score {}, got {}",
+                    expected_score, game_state.score
+                );
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+
+    let mut app = App::new();
+    if let Some(replay) = replay {
+        app.insert_resource(ReplayPlayer::new(replay));
+    }
+
+    app.insert_resource(ClearColor(Color::rgb(0.04, 0.04, 0.04)))
         .insert_resource(WindowDescriptor {
             title: "Space Invaders".to_string(),
-            width: 598.0,
-            height: 676.0,
+            width: config.width,
+            height: config.height,
             ..Default::default()
         })
+        .insert_resource(config)
+        .insert_resource(Bindings::load())
         .add_plugins(DefaultPlugins)
         .add_startup_system(setup_system) // Called once at the beginning of the game
+        .add_plugin(GamePlugin)
+        .add_plugin(GamepadPlugin)
         .add_plugin(PlayerPlugin)
         .add_plugin(EnemyPlugin)
+        .add_plugin(BossPlugin)
+        .add_plugin(BunkerPlugin)
+        .add_plugin(UfoPlugin)
+        .add_plugin(PowerUpPlugin)
+        .add_plugin(ReplayPlugin)
+        .add_plugin(MenuPlugin)
+        .add_plugin(HudPlugin)
         .add_plugin(AudioPlugin)
-        .add_state(AppState::InGame)
-        .add_system_set(
-            SystemSet::on_update(AppState::InGame) // Only run this system when in the InGame state                  
-                .with_system(movable_system)
-                .with_system(player_laser_hit_enemy_system)
-                .with_system(enemy_laser_hit_player_system)
-                .with_system(explosion_to_spawn_system)
-                .with_system(explostion_animation_system)
-        )
         .add_system(main_keyboard_input_system)
+        .add_system(window_focus_system)
         .run();
 }
 
+/// Gameplay shared by the windowed and the headless app
+pub struct GamePlugin;
+
+impl Plugin for GamePlugin {
+    fn build(&self, app: &mut App) {
+        let config = app.world.resource::<GameConfig>();
+        let (seed, headless, mode) = (config.seed, config.headless, config.mode);
+        // without a window, and when watching a replay, there is no menu to start the game from
+        let menuless = headless || config.replay.is_some();
+        let initial_state = if menuless {
+            AppState::InGame
+        } else {
+            AppState::MainMenu
+        };
+
+        let mut simulation = SystemStage::single_threaded();
+        if !headless {
+            simulation = simulation.with_run_criteria(
+                FixedTimestep::step(TIME_STEP as f64).with_label(SIMULATION_TIMESTEP),
+            );
+        }
+
+        app.insert_resource(EnemyCount(0))
+            .insert_resource(SimTime::default())
+            .insert_resource(GameRng::from_seed(seed))
+            .insert_resource(PlayerInput::default())
+            .insert_resource(mode)
+            .add_stage_after(CoreStage::Update, SimulationStage, simulation)
+            .add_state(initial_state)
+            .add_system_set(SystemSet::on_enter(AppState::InGame).with_system(game_setup_system))
+            .add_system_set(SystemSet::on_exit(AppState::InGame).with_system(game_cleanup_system))
+            .add_system_set_to_stage(
+                SimulationStage,
+                SystemSet::new()
+                    .with_run_criteria(in_game) // The clock stands still outside of the game
+                    .label(SimLabel::Clock)
+                    .with_system(sim_time_system),
+            )
+            .add_system_set_to_stage(
+                SimulationStage,
+                SystemSet::new()
+                    .with_run_criteria(in_game) // Only run this system when in the InGame state
+                    .label(SimLabel::Movement)
+                    .after(SimLabel::Fire)
+                    .with_system(movable_system),
+            )
+            .add_system_set_to_stage(
+                SimulationStage,
+                SystemSet::new()
+                    .with_run_criteria(in_game)
+                    .label(SimLabel::Collision)
+                    .after(SimLabel::Movement)
+                    .with_system(player_laser_hit_enemy_system)
+                    .with_system(enemy_laser_hit_player_system),
+            )
+            .add_system_set_to_stage(
+                SimulationStage,
+                SystemSet::new()
+                    .with_run_criteria(in_game)
+                    .label(SimLabel::Effects)
+                    .after(SimLabel::Collision)
+                    .with_system(explosion_to_spawn_system)
+                    .with_system(explostion_animation_system),
+            );
+
+        // ... nor one to go back to once the game is over
+        if menuless {
+            app.add_system_set(
+                SystemSet::on_enter(AppState::GameOver).with_system(game_over_exit_system),
+            );
+        }
+
+        // nothing is drawn when headless, so there is nothing to interpolate
+        if !headless {
+            app.add_system_set_to_stage(
+                SimulationStage,
+                SystemSet::new()
+                    .label(SimLabel::Clock)
+                    .with_system(interpolation_restore_system),
+            )
+            .add_system_set_to_stage(
+                SimulationStage,
+                SystemSet::new()
+                    .label(SimLabel::Snapshot)
+                    .after(SimLabel::Effects)
+                    .with_system(interpolation_snapshot_system),
+            )
+            .add_system_to_stage(
+                CoreStage::PostUpdate,
+                interpolation_render_system
+                    // the fixed timestep keeps going during a pause, the picture must not
+                    .with_run_criteria(in_game)
+                    .before(TransformSystem::TransformPropagate),
+            );
+        }
+    }
+}
+
 fn setup_system(
-    mut commands: Commands, 
+    mut commands: Commands,
     asset_server: Res<AssetServer>,
     mut texture_atlases: ResMut<Assets<TextureAtlas>>,
     mut windows: ResMut<Windows>,
@@ -181,46 +480,229 @@ fn setup_system(
     // add GameTextures resource
     let game_textures = GameTextures {
         player: asset_server.load(PLAYER_SPRITE),
-        player_laser: asset_server.load(PLAYER_LASER_SPRITE),
-        enemy: asset_server.load(ENEMY_SPRITE),
+        weapons: WeaponKind::ALL
+            .into_iter()
+            .map(|kind| (kind, asset_server.load(kind.def().sprite)))
+            .collect(),
+        enemies: EnemyKind::ALL
+            .into_iter()
+            .map(|kind| (kind, asset_server.load(kind.def().sprite)))
+            .collect(),
+        bosses: BossKind::ALL
+            .into_iter()
+            .map(|kind| (kind, asset_server.load(kind.def().sprite)))
+            .collect(),
         enemy_laser: asset_server.load(ENEMY_LASER_SPRITE),
-        explosion: explosion,
+        ufo: asset_server.load(UFO_SPRITE),
+        explosion,
     };
 
     // add GameSounds resource
     let game_sounds = GameSounds {
-        player_laser: asset_server.load(PLAYER_LASER_SOUND),
+        weapons: WeaponKind::ALL
+            .into_iter()
+            .map(|kind| (kind, asset_server.load(kind.def().sound)))
+            .collect(),
         enemy_laser: asset_server.load(ENEMY_LASER_SOUND),
         enemy_explosion: asset_server.load(ENEMY_EXPLOSION_SOUND),
         player_explosion: asset_server.load(PLAYER_EXPLOSION_SOUND),
         player_hit: asset_server.load(PLAYER_HIT_SOUND),
+        ufo: asset_server.load(UFO_SOUND),
+        power_up: asset_server.load(POWER_UP_SOUND),
+    };
+
+    // add GameFonts resource
+    let game_fonts = GameFonts {
+        hud: asset_server.load(HUD_FONT),
     };
 
     commands.insert_resource(game_textures);
     commands.insert_resource(game_sounds);
-    commands.insert_resource(EnemyCount(0));
+    commands.insert_resource(game_fonts);
+}
+
+fn sim_time_system(mut sim_time: ResMut<SimTime>) {
+    sim_time.advance();
+}
+
+/// Puts entities back on their simulated position before the tick moves them on.
+fn interpolation_restore_system(mut query: Query<(&mut Interpolated, &mut Transform)>) {
+    for (mut interpolated, mut transform) in query.iter_mut() {
+        interpolated.previous = interpolated.current;
+        (transform.translation.x, transform.translation.y) = interpolated.current.into();
+    }
+}
+
+/// Records where the tick left every moving entity.
+fn interpolation_snapshot_system(
+    mut commands: Commands,
+    mut query: Query<(&mut Interpolated, &Transform)>,
+    new_query: Query<
+        (Entity, &Transform),
+        (Or<(With<Movable>, With<Enemy>)>, Without<Interpolated>),
+    >,
+) {
+    for (mut interpolated, transform) in query.iter_mut() {
+        interpolated.current = transform.translation.xy();
+    }
+    for (entity, transform) in new_query.iter() {
+        let position = transform.translation.xy();
+        commands.entity(entity).insert(Interpolated {
+            previous: position,
+            current: position,
+        });
+    }
+}
+
+/// Draws every moving entity between its last two simulated positions, so motion stays smooth
+/// when the frame rate is not a multiple of the tick rate.
+fn interpolation_render_system(
+    timesteps: Res<FixedTimesteps>,
+    mut query: Query<(&Interpolated, &mut Transform)>,
+) {
+    let alpha = timesteps
+        .get(SIMULATION_TIMESTEP)
+        .map_or(1., |state| state.overstep_percentage() as f32);
+
+    for (interpolated, mut transform) in query.iter_mut() {
+        let position = interpolated.previous.lerp(interpolated.current, alpha);
+        (transform.translation.x, transform.translation.y) = position.into();
+    }
+}
+
+/// Run criteria - the `SimulationStage` counterpart of `SystemSet::on_update(AppState::InGame)`.
+/// Only `CoreStage::Update` drives the state, so every enter/exit system runs there.
+fn in_game(app_state: Res<State<AppState>>) -> ShouldRun {
+    if *app_state.current() == AppState::InGame {
+        ShouldRun::Yes
+    } else {
+        ShouldRun::No
+    }
+}
+
+/// Run criteria - in game, or on a screen pushed on top of it (`Paused`, `GameOver`).
+fn game_in_stack(app_state: Res<State<AppState>>) -> ShouldRun {
+    if *app_state.current() == AppState::InGame || app_state.inactives().contains(&AppState::InGame)
+    {
+        ShouldRun::Yes
+    } else {
+        ShouldRun::No
+    }
+}
+
+/// Run criteria - runs in game, once every `period` seconds of simulation time.
+fn sim_timestep(
+    period: f64,
+) -> impl FnMut(Res<SimTime>, Res<State<AppState>>, Local<f64>) -> ShouldRun {
+    move |sim_time: Res<SimTime>, app_state: Res<State<AppState>>, mut last_run: Local<f64>| {
+        // every game starts the clock over
+        if sim_time.elapsed < *last_run {
+            *last_run = 0.;
+        }
+        if in_game(app_state) == ShouldRun::Yes && sim_time.elapsed >= *last_run + period {
+            *last_run += period;
+            ShouldRun::Yes
+        } else {
+            ShouldRun::No
+        }
+    }
+}
+
+/// Starts every game from the same clock and seed, so it plays out like its replay.
+fn game_setup_system(
+    config: Res<GameConfig>,
+    mut sim_time: ResMut<SimTime>,
+    mut rng: ResMut<GameRng>,
+    mut enemy_count: ResMut<EnemyCount>,
+    mut input: ResMut<PlayerInput>,
+) {
+    *sim_time = SimTime::default();
+    *rng = GameRng::from_seed(config.seed);
+    *enemy_count = EnemyCount(0);
+    *input = PlayerInput::default();
+}
+
+/// Removes everything the game left on screen, and picks the seed of the next game.
+/// Runs when leaving the game over screen as well, since `GameOver` sits on top of `InGame`.
+fn game_cleanup_system(
+    mut commands: Commands,
+    mut config: ResMut<GameConfig>,
+    query: Query<
+        Entity,
+        Or<(
+            With<Player>,
+            With<Enemy>,
+            With<Laser>,
+            With<Explosion>,
+            With<ExplosionToSpawn>,
+        )>,
+    >,
+) {
+    for entity in query.iter() {
+        commands.entity(entity).despawn();
+    }
+
+    config.seed = rand::random();
+}
+
+fn game_over_exit_system(mut exit: EventWriter<AppExit>) {
+    exit.send(AppExit);
 }
 
 fn movable_system(
     mut commands: Commands,
-	win_size: Res<WinSize>,
-	mut query: Query<(Entity, &Velocity, &mut Transform, &Movable)>,
+    win_size: Res<WinSize>,
+    config: Res<GameConfig>,
+    sim_time: Res<SimTime>,
+    power_up_timers: Res<PowerUpTimers>,
+    mut query: Query<(
+        Entity,
+        &Velocity,
+        &mut Transform,
+        &Movable,
+        Option<&SpriteSize>,
+        Option<&Player>,
+        Option<&FromEnemy>,
+    )>,
 ) {
-    for (entity, velocity, mut transform, movable) in query.iter_mut() {
+    // a time slow holds the enemy bullets back
+    let enemy_bullet_speed = power_up_timers.enemy_bullet_speed(sim_time.elapsed);
+
+    for (entity, velocity, mut transform, movable, size, player, from_enemy) in query.iter_mut() {
+        let scale = transform.scale.xy();
+        let speed = if from_enemy.is_some() {
+            enemy_bullet_speed
+        } else {
+            1.
+        };
         let translation = &mut transform.translation;
-        translation.x += velocity.x * TIME_STEP * BASE_SPEED;
-        translation.y += velocity.y * TIME_STEP * BASE_SPEED;
+        translation.x += velocity.x * speed * TIME_STEP * BASE_SPEED;
+        translation.y += velocity.y * speed * TIME_STEP * BASE_SPEED;
 
         if movable.auto_despawn {
             // despawn if offscreen
             const MARGIN: f32 = 200.;
-            if translation.y > win_size.height / 2. + MARGIN 
-                || translation.y < -win_size.height / 2. - MARGIN 
-                || translation.x > win_size.width / 2. + MARGIN 
-                || translation.x < -win_size.width / 2. - MARGIN 
+            if translation.y > win_size.height / 2. + MARGIN
+                || translation.y < -win_size.height / 2. - MARGIN
+                || translation.x > win_size.width / 2. + MARGIN
+                || translation.x < -win_size.width / 2. - MARGIN
             {
                 commands.entity(entity).despawn();
             }
+        } else if let Some(size) = size {
+            // keep it on screen, and the player in the lower band of it
+            let half_size = size.0 * scale / 2.;
+            let (left, right) = (
+                -win_size.width / 2. + half_size.x,
+                win_size.width / 2. - half_size.x,
+            );
+            let bottom = -win_size.height / 2. + half_size.y;
+            let top = match player {
+                Some(_) => -win_size.height / 2. + win_size.height * config.move_band - half_size.y,
+                None => win_size.height / 2. - half_size.y,
+            };
+            translation.x = translation.x.clamp(left, right.max(left));
+            translation.y = translation.y.clamp(bottom, top.max(bottom));
         }
     }
 }
@@ -228,8 +710,27 @@ fn movable_system(
 fn player_laser_hit_enemy_system(
     mut commands: Commands,
     mut enemy_count: ResMut<EnemyCount>,
-    laser_query: Query<(Entity, &Transform, &SpriteSize), (With<Laser>, With<FromPlayer>)>,
-    enemy_query: Query<(Entity, &Transform, &SpriteSize), With<Enemy>>,
+    mut laser_query: Query<
+        (
+            Entity,
+            &Transform,
+            &SpriteSize,
+            &Damage,
+            Option<&mut Piercing>,
+        ),
+        (With<Laser>, With<FromPlayer>),
+    >,
+    mut enemy_query: Query<
+        (
+            Entity,
+            &Transform,
+            &SpriteSize,
+            &EnemyKind,
+            &mut Attributes,
+            Option<&Armor>,
+        ),
+        With<Enemy>,
+    >,
     mut game_state: ResMut<GameState>,
     game_sounds: Res<GameSounds>,
     audio: Res<Audio>,
@@ -237,19 +738,23 @@ fn player_laser_hit_enemy_system(
     let mut despawned_entities: HashSet<Entity> = HashSet::new();
 
     // iterate over lasers
-    for (laser_entity, laser_tf, laser_size) in laser_query.iter() {
+    for (laser_entity, laser_tf, laser_size, damage, mut piercing) in laser_query.iter_mut() {
         if despawned_entities.contains(&laser_entity) {
             continue;
         }
-        let laser_scale = Vec2::from(laser_tf.scale.xy());
+        let laser_scale = laser_tf.scale.xy();
 
         // iterate over enemies
-        for (enemy_entity, enemy_tf, enemy_size) in enemy_query.iter() {
-            if despawned_entities.contains(&enemy_entity) || despawned_entities.contains(&laser_entity) {
+        for (enemy_entity, enemy_tf, enemy_size, enemy_kind, mut enemy_attributes, enemy_armor) in
+            enemy_query.iter_mut()
+        {
+            if despawned_entities.contains(&enemy_entity)
+                || despawned_entities.contains(&laser_entity)
+            {
                 continue;
             }
 
-            let enemy_scale = Vec2::from(enemy_tf.scale.xy());
+            let enemy_scale = enemy_tf.scale.xy();
 
             // determine if collision
             let collision = collide(
@@ -261,22 +766,40 @@ fn player_laser_hit_enemy_system(
 
             // perform collision action
             if let Some(collision) = collision {
-                // remove laser
-                commands.entity(laser_entity).despawn();
-                despawned_entities.insert(laser_entity);
+                // remove laser, unless it is a beam going on through, damaging every enemy once
+                match piercing.as_mut() {
+                    Some(piercing) if piercing.hit.contains(&enemy_entity) => continue,
+                    Some(piercing) => piercing.hit.push(enemy_entity),
+                    None => {
+                        commands.entity(laser_entity).despawn();
+                        despawned_entities.insert(laser_entity);
+                    }
+                }
+
+                // take damage, tougher enemies survive a few hits
+                enemy_attributes.take_damage(damage, enemy_armor);
+                if !enemy_attributes.is_dead() {
+                    continue;
+                }
 
                 // remove enemy
                 commands.entity(enemy_entity).despawn();
                 despawned_entities.insert(enemy_entity);
                 enemy_count.0 -= 1;
 
-                // spawn explosion
-                commands.spawn().insert(ExplosionToSpawn(enemy_tf.translation.clone()));
+                let enemy_type = enemy_kind.def();
+                // spawn explosion, and maybe a power-up
+                commands.spawn().insert(ExplosionToSpawn(
+                    enemy_tf.translation,
+                    enemy_type.explosion_scale,
+                ));
+                commands
+                    .spawn()
+                    .insert(DropRoll(enemy_tf.translation, *enemy_kind));
                 // Playing the explosion sound
                 audio.play(game_sounds.enemy_explosion.clone());
                 // Updating game state - score
-                game_state.score += 5;
-
+                game_state.score += enemy_type.score;
             }
         }
     }
@@ -285,17 +808,29 @@ fn player_laser_hit_enemy_system(
 fn enemy_laser_hit_player_system(
     mut commands: Commands,
     mut player_state: ResMut<PlayerState>,
-    time: Res<Time>,
-    laser_query: Query<(Entity, &Transform, &SpriteSize), (With<Laser>, With<FromEnemy>)>,
-    mut player_query: Query<(Entity, &Transform, &SpriteSize, &mut Attributes), With<Player>>,
+    mut wave: ResMut<Wave>,
+    sim_time: Res<SimTime>,
+    laser_query: Query<(Entity, &Transform, &SpriteSize, &Damage), (With<Laser>, With<FromEnemy>)>,
+    mut player_query: Query<
+        (
+            Entity,
+            &Transform,
+            &SpriteSize,
+            &mut Attributes,
+            Option<&Armor>,
+        ),
+        With<Player>,
+    >,
     game_sounds: Res<GameSounds>,
     audio: Res<Audio>,
-){
-    if let Ok((player_entity, player_tf, player_size, mut player_attributes)) = player_query.get_single_mut() {
-        let player_scale = Vec2::from(player_tf.scale.xy());
+) {
+    if let Ok((player_entity, player_tf, player_size, mut player_attributes, player_armor)) =
+        player_query.get_single_mut()
+    {
+        let player_scale = player_tf.scale.xy();
 
-        for (laser_entidy, laser_tf, laser_size) in laser_query.iter() {
-            let laser_scale = Vec2::from(laser_tf.scale.xy());
+        for (laser_entidy, laser_tf, laser_size, damage) in laser_query.iter() {
+            let laser_scale = laser_tf.scale.xy();
 
             // determine if collision
             let collision = collide(
@@ -308,25 +843,26 @@ fn enemy_laser_hit_player_system(
             // perform collision action
             if let Some(collision) = collision {
                 // take damage
-                player_attributes.health -= 10.;
-
-                //println!("Player health: {}", player_attributes.health);
+                player_attributes.take_damage(damage, player_armor);
+                wave.hit = true;
 
                 // remove laser
                 commands.entity(laser_entidy).despawn();
 
                 // If players health is 0, spawn explosion and despawn player
-                if player_attributes.health <= 0. {
+                if player_attributes.is_dead() {
                     // remove player
                     commands.entity(player_entity).despawn();
-                    player_state.shot(time.seconds_since_startup());
+                    player_state.shot(sim_time.elapsed);
 
                     // spawn explosion
-                    commands.spawn().insert(ExplosionToSpawn(player_tf.translation.clone()));
+                    commands
+                        .spawn()
+                        .insert(ExplosionToSpawn(player_tf.translation, 1.));
 
                     // Playing the explosion sound
                     audio.play(game_sounds.player_explosion.clone());
-                }else{
+                } else {
                     // Playing the hit sound
                     audio.play(game_sounds.player_hit.clone());
                 }
@@ -345,16 +881,18 @@ fn explosion_to_spawn_system(
 ) {
     for (explosion_spawn_entity, explostion_to_spawn) in query.iter() {
         // spawn the explosion sprite
-        commands.spawn_bundle(SpriteSheetBundle {
-            texture_atlas: game_textures.explosion.clone(),
-            transform: Transform {
-                translation: explostion_to_spawn.0,
+        commands
+            .spawn_bundle(SpriteSheetBundle {
+                texture_atlas: game_textures.explosion.clone(),
+                transform: Transform {
+                    translation: explostion_to_spawn.0,
+                    scale: Vec3::new(explostion_to_spawn.1, explostion_to_spawn.1, 1.),
+                    ..Default::default()
+                },
                 ..Default::default()
-            },
-            ..Default::default()
-        })
-        .insert(Explosion)
-        .insert(ExplosionTimer::default());
+            })
+            .insert(Explosion)
+            .insert(ExplosionTimer::default());
 
         // despawn the explostion to spawn
         commands.entity(explosion_spawn_entity).despawn();
@@ -363,11 +901,10 @@ fn explosion_to_spawn_system(
 
 fn explostion_animation_system(
     mut commands: Commands,
-    time: Res<Time>,
     mut query: Query<(Entity, &mut ExplosionTimer, &mut TextureAtlasSprite), With<Explosion>>,
 ) {
     for (entity, mut timer, mut sprite) in query.iter_mut() {
-        timer.0.tick(time.delta());
+        timer.0.tick(Duration::from_secs_f32(TIME_STEP));
         if timer.0.finished() {
             sprite.index += 1;
             if sprite.index >= EXPLOSION_LENGTH {
@@ -381,21 +918,40 @@ fn main_keyboard_input_system(
     mut commands: Commands,
     mut app_state: ResMut<State<AppState>>,
     keyboard: Res<Input<KeyCode>>,
+    bindings: Res<Bindings>,
+    player_gamepad: Res<PlayerGamepad>,
+    gamepad_buttons: Res<Input<GamepadButton>>,
 ) {
-    // keyboard.just_pressed limits the press to only one time instead of a series of presses
-    if keyboard.just_pressed(KeyCode::P) {
-        match app_state.current() {
-            AppState::MainMenu => {
-                println!("Does nothing");
-            }
-            AppState::InGame => {
-                app_state.set(AppState::Paused).unwrap();
-                println!("paused");
-            }
-            AppState::Paused => {
-                app_state.set(AppState::InGame).unwrap();
-                println!("unpaused");
-            }
+    // just_pressed limits the press to only one time instead of a series of presses
+    if bindings.just_pressed(Action::Pause, &keyboard, player_gamepad.0, &gamepad_buttons) {
+        // Paused is pushed on top of InGame, so resuming does not start a new game.
+        // A transition the menu queued this frame wins over the key.
+        let (transition, message) = match app_state.current() {
+            AppState::InGame => (app_state.push(AppState::Paused), "paused"),
+            AppState::Paused => (app_state.pop(), "unpaused"),
+            _ => return,
+        };
+        if transition.is_ok() {
+            println!("{}", message);
+        }
+    } else if keyboard.just_pressed(KeyCode::Escape) {
+        // the pause screen handles Escape itself
+        if *app_state.current() == AppState::InGame {
+            let _ = app_state.replace(AppState::MainMenu);
         }
     }
-}
\ No newline at end of file
+}
+
+/// Pauses the game when the window loses focus.
+fn window_focus_system(
+    mut focus_events: EventReader<WindowFocused>,
+    mut app_state: ResMut<State<AppState>>,
+) {
+    let unfocused = focus_events.iter().any(|event| !event.focused);
+    if unfocused
+        && *app_state.current() == AppState::InGame
+        && app_state.push(AppState::Paused).is_ok()
+    {
+        println!("paused");
+    }
+}
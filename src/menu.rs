@@ -0,0 +1,481 @@
+use bevy::{app::AppExit, prelude::*};
+use bevy_kira_audio::Audio;
+
+use crate::{
+    bindings::{self, Action, Bindings},
+    config::GameConfig,
+    highscores::HighScores,
+    AppState, GameFonts, GameMode, GameState,
+};
+
+// region: --- Resources
+
+/// Resource - Player settings
+pub struct Settings {
+    pub sound: bool,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self { sound: true }
+    }
+}
+
+/// Resource - Index of the highlighted menu entry
+#[derive(Default)]
+struct MenuSelection(usize);
+
+/// Resource - Action waiting for its new key or button on the controls screen
+#[derive(Default)]
+struct Rebinding(Option<Action>);
+
+// endregion: --- Resources
+
+// region: --- Components
+
+/// Root of the current menu screen, despawned when leaving it
+#[derive(Component)]
+struct MenuUi;
+
+#[derive(Component)]
+struct MenuItem {
+    index: usize,
+    action: MenuAction,
+}
+
+// endregion: --- Components
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum MenuAction {
+    Play,
+    Classic,
+    Settings,
+    HighScores,
+    Quit,
+    ToggleSound,
+    Back,
+    Restart,
+    MainMenu,
+    Resume,
+    QuitToMenu,
+    Controls,
+    Rebind(Action),
+    ResetBindings,
+}
+
+impl MenuAction {
+    fn label(&self, settings: &Settings, bindings: &Bindings, rebinding: &Rebinding) -> String {
+        match self {
+            MenuAction::Play => "Play".to_string(),
+            MenuAction::Classic => "Classic".to_string(),
+            MenuAction::Settings => "Settings".to_string(),
+            MenuAction::HighScores => "High Scores".to_string(),
+            MenuAction::Quit => "Quit".to_string(),
+            MenuAction::ToggleSound => {
+                format!("Sound: {}", if settings.sound { "On" } else { "Off" })
+            }
+            MenuAction::Back => "Back".to_string(),
+            MenuAction::Restart => "Restart".to_string(),
+            MenuAction::MainMenu => "Main Menu".to_string(),
+            MenuAction::Resume => "Resume".to_string(),
+            MenuAction::QuitToMenu => "Quit to Menu".to_string(),
+            MenuAction::Controls => "Controls".to_string(),
+            MenuAction::Rebind(action) if rebinding.0 == Some(*action) => {
+                format!("{}: press a key or button", action.label())
+            }
+            MenuAction::Rebind(action) => {
+                let binding = bindings.get(*action);
+                let keys: Vec<String> = binding
+                    .keys
+                    .iter()
+                    .map(|key| format!("{:?}", key))
+                    .collect();
+                let buttons: Vec<String> = binding
+                    .buttons
+                    .iter()
+                    .map(|button| format!("{:?}", button))
+                    .collect();
+                format!(
+                    "{}: {} | {}",
+                    action.label(),
+                    keys.join(" "),
+                    buttons.join(" ")
+                )
+            }
+            MenuAction::ResetBindings => "Reset Controls".to_string(),
+        }
+    }
+}
+
+pub struct MenuPlugin;
+
+impl Plugin for MenuPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(Settings::default())
+            .insert_resource(HighScores::load())
+            .insert_resource(MenuSelection::default())
+            .insert_resource(Rebinding::default())
+            .add_system_set(SystemSet::on_exit(AppState::InGame).with_system(high_score_system))
+            .add_system(sound_setting_system)
+            .add_system(bindings_save_system)
+            .add_system_set(
+                SystemSet::on_enter(AppState::MainMenu).with_system(main_menu_setup_system),
+            )
+            .add_system_set(
+                SystemSet::on_enter(AppState::Settings).with_system(settings_setup_system),
+            )
+            .add_system_set(
+                SystemSet::on_enter(AppState::Controls).with_system(controls_setup_system),
+            )
+            .add_system_set(
+                SystemSet::on_enter(AppState::HighScores).with_system(high_scores_setup_system),
+            )
+            .add_system_set(
+                SystemSet::on_enter(AppState::GameOver).with_system(game_over_setup_system),
+            )
+            .add_system_set(SystemSet::on_enter(AppState::Paused).with_system(pause_setup_system));
+
+        // every menu screen is navigated and torn down the same way
+        for state in [
+            AppState::MainMenu,
+            AppState::Settings,
+            AppState::Controls,
+            AppState::HighScores,
+            AppState::GameOver,
+            AppState::Paused,
+        ] {
+            app.add_system_set(
+                SystemSet::on_update(state.clone())
+                    .with_system(menu_input_system)
+                    .with_system(menu_highlight_system),
+            )
+            .add_system_set(SystemSet::on_exit(state).with_system(menu_cleanup_system));
+        }
+    }
+}
+
+fn main_menu_setup_system(
+    mut commands: Commands,
+    mut selection: ResMut<MenuSelection>,
+    game_fonts: Res<GameFonts>,
+) {
+    let actions = [
+        MenuAction::Play,
+        MenuAction::Classic,
+        MenuAction::Settings,
+        MenuAction::HighScores,
+        MenuAction::Quit,
+    ];
+    spawn_menu(&mut commands, &game_fonts, "SPACE INVADERS", &[], &actions);
+    selection.0 = 0;
+}
+
+fn settings_setup_system(
+    mut commands: Commands,
+    mut selection: ResMut<MenuSelection>,
+    game_fonts: Res<GameFonts>,
+) {
+    let actions = [
+        MenuAction::ToggleSound,
+        MenuAction::Controls,
+        MenuAction::Back,
+    ];
+    spawn_menu(&mut commands, &game_fonts, "SETTINGS", &[], &actions);
+    selection.0 = 0;
+}
+
+fn controls_setup_system(
+    mut commands: Commands,
+    mut selection: ResMut<MenuSelection>,
+    game_fonts: Res<GameFonts>,
+) {
+    let lines = ["Select an action, then press its new key or button".to_string()];
+    let mut actions: Vec<MenuAction> = Action::ALL.into_iter().map(MenuAction::Rebind).collect();
+    actions.extend([MenuAction::ResetBindings, MenuAction::Back]);
+    spawn_menu(&mut commands, &game_fonts, "CONTROLS", &lines, &actions);
+    selection.0 = 0;
+}
+
+fn high_scores_setup_system(
+    mut commands: Commands,
+    mut selection: ResMut<MenuSelection>,
+    game_fonts: Res<GameFonts>,
+    high_scores: Res<HighScores>,
+) {
+    let mut lines: Vec<String> = high_scores
+        .0
+        .iter()
+        .enumerate()
+        .map(|(rank, score)| format!("{:>2}. {:>6}", rank + 1, score))
+        .collect();
+    if lines.is_empty() {
+        lines.push("No scores yet".to_string());
+    }
+
+    spawn_menu(
+        &mut commands,
+        &game_fonts,
+        "HIGH SCORES",
+        &lines,
+        &[MenuAction::Back],
+    );
+    selection.0 = 0;
+}
+
+fn game_over_setup_system(
+    mut commands: Commands,
+    mut selection: ResMut<MenuSelection>,
+    game_fonts: Res<GameFonts>,
+    game_state: Res<GameState>,
+) {
+    let lines = [format!("Score: {}", game_state.score)];
+    let actions = [MenuAction::Restart, MenuAction::MainMenu];
+    spawn_menu(&mut commands, &game_fonts, "GAME OVER", &lines, &actions);
+    selection.0 = 0;
+}
+
+fn pause_setup_system(
+    mut commands: Commands,
+    mut selection: ResMut<MenuSelection>,
+    game_fonts: Res<GameFonts>,
+) {
+    let actions = [
+        MenuAction::Resume,
+        MenuAction::Restart,
+        MenuAction::QuitToMenu,
+    ];
+    spawn_menu(&mut commands, &game_fonts, "PAUSED", &[], &actions);
+    selection.0 = 0;
+}
+
+/// Spawns a centered column with the title, some plain lines and the selectable entries.
+fn spawn_menu(
+    commands: &mut Commands,
+    game_fonts: &GameFonts,
+    title: &str,
+    lines: &[String],
+    actions: &[MenuAction],
+) {
+    let text_style = |font_size: f32, color: Color| TextStyle {
+        font: game_fonts.hud.clone(),
+        font_size,
+        color,
+    };
+    let text_bundle = |value: String, style: TextStyle| TextBundle {
+        style: Style {
+            margin: Rect::all(Val::Px(8.)),
+            ..default()
+        },
+        text: Text::with_section(value, style, Default::default()),
+        ..default()
+    };
+
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                size: Size::new(Val::Percent(100.), Val::Percent(100.)),
+                // ColumnReverse lays the children out top to bottom
+                flex_direction: FlexDirection::ColumnReverse,
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..default()
+            },
+            color: Color::NONE.into(),
+            ..default()
+        })
+        .insert(MenuUi)
+        .with_children(|parent| {
+            parent.spawn_bundle(text_bundle(
+                title.to_string(),
+                text_style(32., Color::GREEN),
+            ));
+            for line in lines {
+                parent.spawn_bundle(text_bundle(line.clone(), text_style(16., Color::GRAY)));
+            }
+            // the entries are labelled by `menu_highlight_system`
+            for (index, action) in actions.iter().enumerate() {
+                parent
+                    .spawn_bundle(text_bundle(String::new(), text_style(20., Color::WHITE)))
+                    .insert(MenuItem {
+                        index,
+                        action: *action,
+                    });
+            }
+        });
+}
+
+fn menu_cleanup_system(mut commands: Commands, query: Query<Entity, With<MenuUi>>) {
+    for entity in query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+/// Moves the selection and runs the selected entry, with the keyboard or any gamepad.
+fn menu_input_system(
+    keyboard: Res<Input<KeyCode>>,
+    gamepads: Res<Gamepads>,
+    gamepad_buttons: Res<Input<GamepadButton>>,
+    mut selection: ResMut<MenuSelection>,
+    mut app_state: ResMut<State<AppState>>,
+    mut settings: ResMut<Settings>,
+    mut bindings: ResMut<Bindings>,
+    mut rebinding: ResMut<Rebinding>,
+    mut game_mode: ResMut<GameMode>,
+    config: Res<GameConfig>,
+    mut exit: EventWriter<AppExit>,
+    query: Query<&MenuItem>,
+) {
+    // the controls screen waits for a new binding, nothing else until it gets one
+    if let Some(action) = rebinding.0 {
+        if keyboard.just_pressed(KeyCode::Escape) {
+            rebinding.0 = None;
+        } else if let Some(key) = bindings::just_pressed_key(&keyboard) {
+            bindings.bind_key(action, key);
+            rebinding.0 = None;
+        } else if let Some(button) = gamepads
+            .iter()
+            .find_map(|&gamepad| bindings::just_pressed_button(gamepad, &gamepad_buttons))
+        {
+            bindings.bind_button(action, button);
+            rebinding.0 = None;
+        }
+        return;
+    }
+
+    let pad_pressed = |button_type: GamepadButtonType| {
+        gamepads
+            .iter()
+            .any(|&gamepad| gamepad_buttons.just_pressed(GamepadButton(gamepad, button_type)))
+    };
+
+    let up = keyboard.any_just_pressed([KeyCode::Up, KeyCode::W])
+        || pad_pressed(GamepadButtonType::DPadUp);
+    let down = keyboard.any_just_pressed([KeyCode::Down, KeyCode::S])
+        || pad_pressed(GamepadButtonType::DPadDown);
+    let confirm = keyboard.any_just_pressed([KeyCode::Return, KeyCode::Space])
+        || pad_pressed(GamepadButtonType::South);
+    let back = keyboard.just_pressed(KeyCode::Escape) || pad_pressed(GamepadButtonType::East);
+
+    let count = query.iter().count();
+    if count == 0 {
+        return;
+    }
+
+    if up {
+        selection.0 = (selection.0 + count - 1) % count;
+    } else if down {
+        selection.0 = (selection.0 + 1) % count;
+    }
+
+    let action = if confirm {
+        query
+            .iter()
+            .find(|item| item.index == selection.0)
+            .map(|item| item.action)
+    } else if back {
+        match app_state.current() {
+            AppState::MainMenu => None,
+            AppState::Paused => Some(MenuAction::Resume),
+            _ => Some(MenuAction::Back),
+        }
+    } else {
+        None
+    };
+
+    let transition = match action {
+        Some(MenuAction::Play) => {
+            // the mode of the command line, `--mode classic` included
+            *game_mode = config.mode;
+            app_state.set(AppState::InGame)
+        }
+        Some(MenuAction::Classic) => {
+            *game_mode = GameMode::Classic;
+            app_state.set(AppState::InGame)
+        }
+        Some(MenuAction::Settings) => app_state.set(AppState::Settings),
+        Some(MenuAction::HighScores) => app_state.set(AppState::HighScores),
+        Some(MenuAction::Controls) => app_state.set(AppState::Controls),
+        Some(MenuAction::Back) if *app_state.current() == AppState::Controls => {
+            app_state.set(AppState::Settings)
+        }
+        // replace, so leaving the game over or pause screen also leaves the game underneath it
+        Some(MenuAction::Back | MenuAction::MainMenu | MenuAction::QuitToMenu) => {
+            app_state.replace(AppState::MainMenu)
+        }
+        Some(MenuAction::Restart) => app_state.replace(AppState::InGame),
+        Some(MenuAction::Resume) => app_state.pop(),
+        Some(MenuAction::Quit) => {
+            exit.send(AppExit);
+            Ok(())
+        }
+        Some(MenuAction::ToggleSound) => {
+            settings.sound = !settings.sound;
+            Ok(())
+        }
+        Some(MenuAction::Rebind(action)) => {
+            rebinding.0 = Some(action);
+            Ok(())
+        }
+        Some(MenuAction::ResetBindings) => {
+            *bindings = Bindings::default();
+            Ok(())
+        }
+        None => Ok(()),
+    };
+    // the pause key may have queued a transition this frame already, that one wins
+    if let Err(err) = transition {
+        warn!("menu action {:?} ignored: {:?}", action, err);
+    }
+}
+
+/// Highlights the selected entry and keeps the labels in sync with the settings and bindings.
+fn menu_highlight_system(
+    selection: Res<MenuSelection>,
+    settings: Res<Settings>,
+    bindings: Res<Bindings>,
+    rebinding: Res<Rebinding>,
+    mut query: Query<(&MenuItem, &mut Text)>,
+    added_query: Query<(), Added<MenuItem>>,
+) {
+    let changed = selection.is_changed()
+        || settings.is_changed()
+        || bindings.is_changed()
+        || rebinding.is_changed();
+    if !changed && added_query.is_empty() {
+        return;
+    }
+
+    for (item, mut text) in query.iter_mut() {
+        let section = &mut text.sections[0];
+        section.value = item.action.label(&settings, &bindings, &rebinding);
+        section.style.color = if item.index == selection.0 {
+            Color::YELLOW
+        } else {
+            Color::WHITE
+        };
+    }
+}
+
+fn sound_setting_system(settings: Res<Settings>, audio: Res<Audio>) {
+    if settings.is_changed() {
+        audio.set_volume(if settings.sound { 1. } else { 0. });
+    }
+}
+
+fn bindings_save_system(bindings: Res<Bindings>) {
+    // loaded from the file, or defaults, when added
+    if bindings.is_changed() && !bindings.is_added() {
+        if let Err(err) = bindings.save() {
+            eprintln!("error: could not save bindings: {}", err);
+        }
+    }
+}
+
+/// Enters the score of the game that just ended into the high score table.
+fn high_score_system(game_state: Res<GameState>, mut high_scores: ResMut<HighScores>) {
+    if high_scores.insert(game_state.score) {
+        if let Err(err) = high_scores.save() {
+            eprintln!("error: could not save high scores: {}", err);
+        }
+    }
+}
@@ -1,5 +1,17 @@
-use crate::{GameTextures, WinSize, PLAYER_SIZE, SPRITE_SCALE, PLAYER_LASER_SIZE, components::{Velocity, Player, Movable, FromPlayer, SpriteSize, Laser, Attributes, HealthText, ScoreText}, TIME_STEP, BASE_SPEED, PlayerState, GameState, PLAYER_RESPAWN_DELAY, AppState, GameSounds};
-use bevy::{prelude::*, input::keyboard, core::FixedTimestep};
+use crate::{
+    bindings::{Action, Bindings},
+    components::{Attributes, FromPlayer, Laser, Movable, Player, SpriteSize, Velocity},
+    config::GameConfig,
+    gamepad::{stick_input, PlayerGamepad},
+    in_game,
+    powerup::PowerUpTimers,
+    sim_timestep,
+    weapon::{fire_weapon, seeker_system, Weapon, WeaponKind},
+    AppState, GameMode, GameSounds, GameState, GameTextures, PlayerInput, PlayerState, SimLabel,
+    SimTime, SimulationStage, WinSize, BASE_SPEED, PLAYER_RESPAWN_DELAY, PLAYER_SIZE, SPRITE_SCALE,
+    TIME_STEP,
+};
+use bevy::{input::keyboard, math::Vec3Swizzles, prelude::*};
 use bevy_kira_audio::{Audio, AudioPlugin};
 
 pub struct PlayerPlugin;
@@ -7,199 +19,210 @@ pub struct PlayerPlugin;
 impl Plugin for PlayerPlugin {
     fn build(&self, app: &mut App) {
         app.insert_resource(PlayerState::default())
-            .insert_resource(Attributes::default()) // This is neede to access the attributes from the player
             .insert_resource(GameState::default()) // This is needed to access the game state from the player
-			.add_system_set(
-				SystemSet::new()
-					.with_run_criteria(FixedTimestep::step(0.5))
-					.with_system(player_spawn_system),
-			)
+            .add_system_set_to_stage(
+                SimulationStage,
+                SystemSet::new()
+                    .with_run_criteria(sim_timestep(0.5))
+                    .label(SimLabel::Spawn)
+                    .after(SimLabel::Clock)
+                    .with_system(player_spawn_system)
+                    .with_system(player_game_over_system),
+            )
+            .add_system_set(SystemSet::on_enter(AppState::InGame).with_system(player_reset_system))
+            .add_system_set_to_stage(
+                SimulationStage,
+                SystemSet::new()
+                    .with_run_criteria(in_game) // Only run this system when in the InGame state
+                    .label(SimLabel::Input)
+                    .after(SimLabel::Spawn)
+                    .with_system(player_keyboard_event_system),
+            )
+            .add_system_set_to_stage(
+                SimulationStage,
+                SystemSet::new()
+                    .with_run_criteria(in_game)
+                    .label(SimLabel::Fire)
+                    .after(SimLabel::Input)
+                    .with_system(player_fire_system)
+                    .with_system(seeker_system),
+            )
             .add_system_set(
                 SystemSet::on_update(AppState::InGame) // Only run this system when in the InGame state
-                .with_system(player_keyboard_event_system)
-                .with_system(player_fire_system)
-                .with_system(health_text_update_system)
-                .with_system(score_text_update_system)
+                    .with_system(player_input_system),
             );
-	}
-}
-
-/// This system is responsible for updating the value of the player's health.
-fn health_text_update_system(
-    player_query: Query<(Entity, &Attributes), With<Player>>, 
-    mut query: Query<&mut Text, With<HealthText>>
-) {
-    if let Ok((player_entity, player_attributes)) = player_query.get_single() {
-        for mut text in query.iter_mut() {
-            let new_health = format!("Health: {}", player_attributes.health);
-            // We used the `Text::with_section` helper method, but it is still just a `Text`,
-            // so to update it, we are still updating the one and only section
-            text.sections[0].value = new_health;
-        }
     }
 }
 
-/// This system is responsible for updating the value of the player's score.
-fn score_text_update_system(
-    mut game_state: ResMut<GameState>,
-    mut query: Query<&mut Text, With<ScoreText>>
-) {
-    for mut text in query.iter_mut() {
-        let new_score = format!("Score: {}", game_state.score);
-        // We used the `Text::with_section` helper method, but it is still just a `Text`,
-        // so to update it, we are still updating the one and only section
-        text.sections[0].value = new_score;
-    }
+/// Starts a new game with a fresh player and score.
+fn player_reset_system(mut player_state: ResMut<PlayerState>, mut game_state: ResMut<GameState>) {
+    *player_state = PlayerState::default();
+    *game_state = GameState::default();
 }
 
 fn player_spawn_system(
     mut commands: Commands,
     mut player_state: ResMut<PlayerState>,
-    mut game_state: ResMut<GameState>,
-    player_attributes: Res<Attributes>, // This is needed to access the attributes from the player
-    time: Res<Time>,
+    sim_time: Res<SimTime>,
     game_textures: Res<GameTextures>,
+    game_mode: Res<GameMode>,
     mut win_size: ResMut<WinSize>,
-    asset_server: Res<AssetServer>,
 ) {
-    let now = time.seconds_since_startup();
-	let last_shot = player_state.last_shot;
+    let now = sim_time.elapsed;
+    let last_shot = player_state.last_shot;
 
-	if !player_state.on && (last_shot == -1. || now > last_shot + PLAYER_RESPAWN_DELAY) {
-		// add player
-		let bottom = -win_size.height / 2.;
-		commands
-			.spawn_bundle(SpriteBundle {
-				texture: game_textures.player.clone(),
-				transform: Transform {
-					translation: Vec3::new(
-						0.,
-						bottom + PLAYER_SIZE.1 / 2. * SPRITE_SCALE + 5.,
-						10.,
-					),
-					scale: Vec3::new(SPRITE_SCALE, SPRITE_SCALE, 1.),
-					..Default::default()
-				},
-				..Default::default()
-			})
-			.insert(Player)
-			.insert(SpriteSize::from(PLAYER_SIZE))
-			.insert(Movable { auto_despawn: false })
-			.insert(Velocity { x: 0., y: 0. })
-            .insert(Attributes::default());
+    if !player_state.on
+        && player_state.lives > 0
+        && (last_shot == -1. || now > last_shot + PLAYER_RESPAWN_DELAY)
+    {
+        // every ship starts with the twin laser, the classic cannon only ever has the single one
+        let weapon = if *game_mode == GameMode::Classic {
+            WeaponKind::Single
+        } else {
+            WeaponKind::Twin
+        };
 
-        // add health text to top left of screen
+        // add player
+        let bottom = -win_size.height / 2.;
         commands
-            .spawn_bundle(TextBundle {
-                style: Style {
-                    align_self: AlignSelf::FlexEnd,
-                    position_type: PositionType::Absolute,
-                    position: Rect {
-                        top: Val::Px(15.0),
-                        left: Val::Px(15.0),
-                        ..default()
-                    },
-                    ..default()
+            .spawn_bundle(SpriteBundle {
+                texture: game_textures.player.clone(),
+                transform: Transform {
+                    translation: Vec3::new(
+                        0.,
+                        bottom + PLAYER_SIZE.1 / 2. * SPRITE_SCALE + 5.,
+                        10.,
+                    ),
+                    scale: Vec3::new(SPRITE_SCALE, SPRITE_SCALE, 1.),
+                    ..Default::default()
                 },
-                // Use the `Text::with_section` constructor
-                text: Text::with_section(
-                    // Accepts a `String` or any type that converts into a `String`, such as `&str`
-                    format!("Health: {}", player_attributes.health.to_string()),
-                    
-                    TextStyle {
-                        font: asset_server.load("fonts/AgentOrange.ttf"),
-                        font_size: 16.0,
-                        color: Color::GREEN,
-                    },
-                    Default::default()
-                ),
-                ..default()
+                ..Default::default()
             })
-            .insert(HealthText);
-        commands
-            .spawn_bundle(TextBundle {
-                style: Style {
-                    align_self: AlignSelf::FlexEnd,
-                    position_type: PositionType::Absolute,
-                    position: Rect {
-                        top: Val::Px(15.0),
-                        right: Val::Px(15.0),
-                        ..default()
-                    },
-                    ..default()
-                },
-                // Use the `Text::with_section` constructor
-                text: Text::with_section(
-                    // Accepts a `String` or any type that converts into a `String`, such as `&str`
-                    format!("Score: {}", game_state.score.to_string()),
-                    
-                    TextStyle {
-                        font: asset_server.load("fonts/AgentOrange.ttf"),
-                        font_size: 16.0,
-                        color: Color::BLUE,
-                    },
-                    Default::default()
-                ),
-                ..default()
+            .insert(Player)
+            .insert(SpriteSize::from(PLAYER_SIZE))
+            .insert(Movable {
+                auto_despawn: false,
             })
-            .insert(ScoreText);
+            .insert(Velocity { x: 0., y: 0. })
+            .insert(Attributes::default())
+            .insert(Weapon::new(weapon));
+
+        player_state.spawned();
+    }
+}
 
-		player_state.spawned();
-	}
+/// Ends the game once the last life is lost and its explosion has played out.
+fn player_game_over_system(
+    player_state: Res<PlayerState>,
+    sim_time: Res<SimTime>,
+    mut app_state: ResMut<State<AppState>>,
+) {
+    if !player_state.on
+        && player_state.lives == 0
+        && sim_time.elapsed > player_state.last_shot + PLAYER_RESPAWN_DELAY
+    {
+        // fails if a pause or quit is already queued, the next check tries again
+        let _ = app_state.push(AppState::GameOver);
+    }
 }
 
+/// Fires the weapon of the player on a press, or for as long as fire is held, once it has reloaded,
+/// and switches weapons on a press.
 fn player_fire_system(
     mut commands: Commands,
     game_textures: Res<GameTextures>,
     game_sounds: Res<GameSounds>,
-    mut keyboard: Res<Input<KeyCode>>,
-    query: Query<&Transform, With<Player>>,
+    mut input: ResMut<PlayerInput>,
+    mut player_state: ResMut<PlayerState>,
+    game_mode: Res<GameMode>,
+    sim_time: Res<SimTime>,
+    mut query: Query<(&Transform, &mut Weapon), With<Player>>,
+    laser_query: Query<(), (With<Laser>, With<FromPlayer>)>,
     audio: Res<Audio>,
 ) {
-   if let Ok(player_tf) = query.get_single() {
-    if keyboard.just_pressed(KeyCode::Space) {
-        let (x, y) = (player_tf.translation.x, player_tf.translation.y);
-        let x_offset = PLAYER_SIZE.0 / 2. * SPRITE_SCALE - 5.;
+    // latched presses are used up by this tick, even if the player is not there to fire
+    let fire = std::mem::take(&mut input.fire);
+    let switch_weapon = std::mem::take(&mut input.switch_weapon);
 
-        // Creating a closure to spawn a laser
-        let mut spawn_laser = |x_offset: f32| {
-            commands.spawn_bundle(SpriteBundle {
-                texture: game_textures.player_laser.clone(),
-                transform: Transform {
-                    translation: Vec3::new(x + x_offset, y + 15., 0.0),
-                    scale: Vec3::new(SPRITE_SCALE, SPRITE_SCALE, 1.0),
-                    ..Default::default()
-                },
-                ..Default::default()
-            })
-            .insert(Laser)
-            .insert(FromPlayer)
-            .insert(SpriteSize::from(PLAYER_LASER_SIZE))
-            .insert(Movable{auto_despawn: true})
-            .insert(Velocity{x: 0., y: 1.});
-        };
+    if let Ok((player_tf, mut weapon)) = query.get_single_mut() {
+        // the classic cannon has one weapon, fired a press at a time, with one shot on screen at a time
+        let classic = *game_mode == GameMode::Classic;
+        if switch_weapon && !classic {
+            let next = weapon.kind.next();
+            weapon.switch_to(next);
+        }
+        let trigger = fire || (input.fire_held && !classic);
+        let loaded = (!classic || laser_query.is_empty()) && sim_time.elapsed >= weapon.next_shot;
+
+        if trigger && loaded {
+            let kind = weapon.kind;
+            player_state.shots += 1;
 
-        // Spawning the lasers - one on the left and one on the right
-        spawn_laser(-x_offset);
-        spawn_laser(x_offset);
-        
-        // Playing the laser sound
-        audio.play(game_sounds.player_laser.clone());
+            weapon.next_shot = fire_weapon(
+                &mut commands,
+                &game_textures,
+                &sim_time,
+                kind,
+                player_tf.translation.xy(),
+            );
+
+            // Playing the sound of the weapon
+            audio.play(game_sounds.weapons.get(&kind).cloned().unwrap_or_default());
+        }
     }
-   }
 }
 
-fn player_keyboard_event_system(
+/// Samples the keyboard and gamepad once per frame into the `PlayerInput` of the next simulation tick.
+fn player_input_system(
     keyboard: Res<Input<KeyCode>>,
-    mut query: Query<&mut Velocity, With<Player>>
+    bindings: Res<Bindings>,
+    player_gamepad: Res<PlayerGamepad>,
+    gamepad_buttons: Res<Input<GamepadButton>>,
+    gamepad_axes: Res<Axis<GamepadAxis>>,
+    config: Res<GameConfig>,
+    mut input: ResMut<PlayerInput>,
+) {
+    let gamepad = player_gamepad.0;
+    let pressed = |action| bindings.pressed(action, &keyboard, gamepad, &gamepad_buttons);
+    input.left = pressed(Action::MoveLeft);
+    input.right = pressed(Action::MoveRight);
+    input.up = pressed(Action::MoveUp);
+    input.down = pressed(Action::MoveDown);
+    // the stick is not bound to an action, it always moves the player once out of the dead zone
+    input.stick = stick_input(player_gamepad.stick(&gamepad_axes), config.dead_zone);
+    // keep the presses until a tick has used them
+    input.fire |= bindings.just_pressed(Action::Fire, &keyboard, gamepad, &gamepad_buttons);
+    input.fire_held = pressed(Action::Fire);
+    input.switch_weapon |=
+        bindings.just_pressed(Action::SwitchWeapon, &keyboard, gamepad, &gamepad_buttons);
+}
+
+fn player_keyboard_event_system(
+    input: Res<PlayerInput>,
+    game_mode: Res<GameMode>,
+    sim_time: Res<SimTime>,
+    power_up_timers: Res<PowerUpTimers>,
+    mut query: Query<&mut Velocity, With<Player>>,
 ) {
     if let Ok(mut velocity) = query.get_single_mut() {
-        velocity.x = if keyboard.pressed(KeyCode::A) {
-            -1.
-        } else if keyboard.pressed(KeyCode::D) {
-            1.
-        } else {
+        let axis = |negative: bool, positive: bool| positive as i8 as f32 - negative as i8 as f32;
+        // the classic cannon only moves sideways
+        let classic = *game_mode == GameMode::Classic;
+        let vertical = if classic {
             0.
-        }
+        } else {
+            axis(input.down, input.up)
+        };
+        let stick = if classic {
+            Vec2::new(input.stick.x, 0.)
+        } else {
+            input.stick
+        };
+        // diagonals are no faster than straight moves, and the stick moves as fast as it is tilted
+        let keys = Vec2::new(axis(input.left, input.right), vertical).normalize_or_zero();
+        let direction = (keys + stick).clamp_length_max(1.);
+        let speed = power_up_timers.player_speed(sim_time.elapsed);
+        velocity.x = direction.x * speed;
+        velocity.y = direction.y * speed;
     }
-}
\ No newline at end of file
+}
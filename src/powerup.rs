@@ -0,0 +1,387 @@
+use bevy::{math::Vec3Swizzles, prelude::*, sprite::collide_aabb::collide, utils::HashMap};
+use bevy_kira_audio::Audio;
+use rand::Rng;
+
+use crate::{
+    components::{
+        Armor, Attributes, Enemy, ExplosionToSpawn, FromEnemy, Laser, Movable, Player, SpriteSize,
+        Velocity,
+    },
+    enemy::EnemyKind,
+    in_game,
+    weapon::Weapon,
+    AppState, EnemyCount, GameFonts, GameRng, GameSounds, GameState, PlayerState, SimLabel,
+    SimTime, SimulationStage,
+};
+
+const POWER_UP_SIZE: (f32, f32) = (24., 24.);
+const POWER_UP_SPEED: f32 = 0.15; // multiplier of the base speed, falling
+const HEALTH_RESTORE: f32 = 50.;
+const SHIELD_ARMOR: Armor = Armor {
+    resist: 0.75,
+    flat: 0.,
+};
+const SHIELD_COLOR: Color = Color::rgb(0.5, 0.9, 1.);
+const SPEED_BOOST: f32 = 1.5; // multiplier of the player speed
+const TIME_SLOW: f32 = 0.35; // multiplier of the enemy bullet speed
+
+/// Definition of a power-up, see `PowerUpKind::def`
+pub struct PowerUpDef {
+    pub name: &'static str,
+    pub letter: &'static str, // drawn falling
+    pub color: Color,
+    pub duration: Option<f64>, // seconds, for the ones that wear off
+}
+
+const POWER_UPS: [PowerUpDef; 7] = [
+    PowerUpDef {
+        name: "Weapon",
+        letter: "W",
+        color: Color::ORANGE,
+        duration: None,
+    },
+    PowerUpDef {
+        name: "Shield",
+        letter: "S",
+        color: SHIELD_COLOR,
+        duration: Some(8.),
+    },
+    PowerUpDef {
+        name: "Health",
+        letter: "H",
+        color: Color::GREEN,
+        duration: None,
+    },
+    PowerUpDef {
+        name: "Life",
+        letter: "L",
+        color: Color::PINK,
+        duration: None,
+    },
+    PowerUpDef {
+        name: "Speed",
+        letter: "V",
+        color: Color::YELLOW,
+        duration: Some(8.),
+    },
+    PowerUpDef {
+        name: "Slow",
+        letter: "T",
+        color: Color::PURPLE,
+        duration: Some(6.),
+    },
+    PowerUpDef {
+        name: "Bomb",
+        letter: "B",
+        color: Color::RED,
+        duration: None,
+    },
+];
+
+// region: --- Components
+
+/// Component - Power-up falling from a destroyed enemy, the registry of power-ups is `POWER_UPS`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Component)]
+pub enum PowerUpKind {
+    Weapon,
+    Shield,
+    Health,
+    ExtraLife,
+    Speed,
+    TimeSlow,
+    Bomb,
+}
+
+impl PowerUpKind {
+    pub const ALL: [PowerUpKind; 7] = [
+        PowerUpKind::Weapon,
+        PowerUpKind::Shield,
+        PowerUpKind::Health,
+        PowerUpKind::ExtraLife,
+        PowerUpKind::Speed,
+        PowerUpKind::TimeSlow,
+        PowerUpKind::Bomb,
+    ];
+
+    pub fn def(&self) -> &'static PowerUpDef {
+        &POWER_UPS[*self as usize]
+    }
+}
+
+/// Component - Destroyed enemy rolling its drop table, at the translation
+#[derive(Component)]
+pub struct DropRoll(pub Vec3, pub EnemyKind);
+
+/// Component - Bomb to go off, clearing the screen
+#[derive(Component)]
+struct Bomb;
+
+// endregion: --- Components
+
+// region: --- Resources
+
+/// Resource - Sim time every power-up of the player that wears off runs out at
+#[derive(Default)]
+pub struct PowerUpTimers(HashMap<PowerUpKind, f64>);
+
+impl PowerUpTimers {
+    pub fn is_active(&self, kind: PowerUpKind, now: f64) -> bool {
+        self.0.get(&kind).is_some_and(|until| now < *until)
+    }
+
+    /// Seconds left of every active power-up, in the order of `PowerUpKind::ALL`
+    pub fn remaining(&self, now: f64) -> impl Iterator<Item = (PowerUpKind, f64)> + '_ {
+        PowerUpKind::ALL
+            .into_iter()
+            .filter_map(move |kind| self.0.get(&kind).map(|until| (kind, until - now)))
+            .filter(|(_, left)| *left > 0.)
+    }
+
+    /// Multiplier of the player speed
+    pub fn player_speed(&self, now: f64) -> f32 {
+        if self.is_active(PowerUpKind::Speed, now) {
+            SPEED_BOOST
+        } else {
+            1.
+        }
+    }
+
+    /// Multiplier of the enemy bullet speed
+    pub fn enemy_bullet_speed(&self, now: f64) -> f32 {
+        if self.is_active(PowerUpKind::TimeSlow, now) {
+            TIME_SLOW
+        } else {
+            1.
+        }
+    }
+}
+
+// endregion: --- Resources
+
+/// Power-ups dropped by destroyed enemies, at random from the drop table of their type.
+/// The ones that wear off are lost with the ship.
+pub struct PowerUpPlugin;
+
+impl Plugin for PowerUpPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(PowerUpTimers::default())
+            .add_system_set(
+                SystemSet::on_enter(AppState::InGame).with_system(power_up_reset_system),
+            )
+            .add_system_set(
+                SystemSet::on_exit(AppState::InGame).with_system(power_up_cleanup_system),
+            )
+            .add_system_set_to_stage(
+                SimulationStage,
+                SystemSet::new()
+                    .with_run_criteria(in_game)
+                    .label(SimLabel::Collision)
+                    .after(SimLabel::Movement)
+                    .with_system(power_up_pickup_system),
+            )
+            .add_system_set_to_stage(
+                SimulationStage,
+                SystemSet::new()
+                    .with_run_criteria(in_game)
+                    .label(SimLabel::Effects)
+                    .after(SimLabel::Collision)
+                    .with_system(drop_roll_system)
+                    .with_system(bomb_system)
+                    .with_system(shield_system),
+            );
+    }
+}
+
+fn power_up_reset_system(mut timers: ResMut<PowerUpTimers>) {
+    *timers = PowerUpTimers::default();
+}
+
+fn power_up_cleanup_system(
+    mut commands: Commands,
+    query: Query<Entity, Or<(With<PowerUpKind>, With<DropRoll>, With<Bomb>)>>,
+) {
+    for entity in query.iter() {
+        commands.entity(entity).despawn();
+    }
+}
+
+/// Rolls the drop table of every destroyed enemy, and drops what comes up, if anything.
+fn drop_roll_system(
+    mut commands: Commands,
+    mut rng: ResMut<GameRng>,
+    game_fonts: Res<GameFonts>,
+    query: Query<(Entity, &DropRoll)>,
+) {
+    for (entity, DropRoll(translation, enemy_kind)) in query.iter() {
+        commands.entity(entity).despawn();
+
+        let drops = enemy_kind.def().drops;
+        if drops.is_empty() {
+            continue;
+        }
+        // one roll over the chances of the table, so an enemy drops one power-up at most
+        let mut roll = rng.0.gen::<f64>();
+        let dropped = drops.iter().find(|(_, chance)| {
+            roll -= chance;
+            roll < 0.
+        });
+        if let Some(&(kind, _)) = dropped {
+            spawn_power_up(&mut commands, &game_fonts, kind, *translation);
+        }
+    }
+}
+
+fn spawn_power_up(
+    commands: &mut Commands,
+    game_fonts: &GameFonts,
+    kind: PowerUpKind,
+    translation: Vec3,
+) {
+    let def = kind.def();
+    commands
+        .spawn_bundle(Text2dBundle {
+            text: Text::with_section(
+                def.letter,
+                TextStyle {
+                    font: game_fonts.hud.clone(),
+                    font_size: 24.,
+                    color: def.color,
+                },
+                TextAlignment {
+                    vertical: VerticalAlign::Center,
+                    horizontal: HorizontalAlign::Center,
+                },
+            ),
+            transform: Transform::from_translation(translation.truncate().extend(15.)),
+            ..Default::default()
+        })
+        .insert(kind)
+        .insert(SpriteSize::from(POWER_UP_SIZE))
+        .insert(Movable { auto_despawn: true })
+        .insert(Velocity {
+            x: 0.,
+            y: -POWER_UP_SPEED,
+        });
+}
+
+/// Collects the power-ups the player runs into.
+fn power_up_pickup_system(
+    mut commands: Commands,
+    mut timers: ResMut<PowerUpTimers>,
+    mut player_state: ResMut<PlayerState>,
+    sim_time: Res<SimTime>,
+    mut player_query: Query<(&Transform, &SpriteSize, &mut Attributes, &mut Weapon), With<Player>>,
+    power_up_query: Query<(Entity, &Transform, &SpriteSize, &PowerUpKind)>,
+    game_sounds: Res<GameSounds>,
+    audio: Res<Audio>,
+) {
+    let (player_tf, player_size, mut attributes, mut weapon) = match player_query.get_single_mut() {
+        Ok(player) => player,
+        Err(_) => return,
+    };
+
+    for (entity, power_up_tf, power_up_size, kind) in power_up_query.iter() {
+        let collision = collide(
+            power_up_tf.translation,
+            power_up_size.0 * power_up_tf.scale.xy(),
+            player_tf.translation,
+            player_size.0 * player_tf.scale.xy(),
+        );
+        if collision.is_none() {
+            continue;
+        }
+
+        commands.entity(entity).despawn();
+        audio.play(game_sounds.power_up.clone());
+
+        match kind {
+            PowerUpKind::Weapon => {
+                let upgraded = weapon.kind.upgraded();
+                weapon.switch_to(upgraded);
+            }
+            PowerUpKind::Health => {
+                attributes.health = (attributes.health + HEALTH_RESTORE).min(attributes.max_health);
+            }
+            PowerUpKind::ExtraLife => player_state.lives += 1,
+            PowerUpKind::Bomb => {
+                commands.spawn().insert(Bomb);
+            }
+            // the ones that wear off start over when picked up again
+            PowerUpKind::Shield | PowerUpKind::Speed | PowerUpKind::TimeSlow => {
+                let duration = kind.def().duration.unwrap_or_default();
+                timers.0.insert(*kind, sim_time.elapsed + duration);
+            }
+        }
+    }
+}
+
+/// Destroys every enemy of the wave, the bosses aside, and every enemy bullet.
+fn bomb_system(
+    mut commands: Commands,
+    mut enemy_count: ResMut<EnemyCount>,
+    mut game_state: ResMut<GameState>,
+    bomb_query: Query<Entity, With<Bomb>>,
+    // bosses and their zones have no `EnemyKind`
+    enemy_query: Query<(Entity, &Transform, &EnemyKind, &Attributes), With<Enemy>>,
+    laser_query: Query<Entity, (With<Laser>, With<FromEnemy>)>,
+    game_sounds: Res<GameSounds>,
+    audio: Res<Audio>,
+) {
+    if bomb_query.is_empty() {
+        return;
+    }
+    for entity in bomb_query.iter() {
+        commands.entity(entity).despawn();
+    }
+
+    // enemies the lasers took down this tick are already counted
+    for (entity, transform, kind, attributes) in enemy_query
+        .iter()
+        .filter(|(.., attributes)| !attributes.is_dead())
+    {
+        let enemy_type = kind.def();
+        commands.entity(entity).despawn();
+        commands.spawn().insert(ExplosionToSpawn(
+            transform.translation,
+            enemy_type.explosion_scale,
+        ));
+        enemy_count.0 -= 1;
+        game_state.score += enemy_type.score;
+    }
+    for entity in laser_query.iter() {
+        commands.entity(entity).despawn();
+    }
+    audio.play(game_sounds.player_explosion.clone());
+}
+
+/// Shields the ship while the shield lasts, and drops the power-ups that wear off with the ship.
+fn shield_system(
+    mut commands: Commands,
+    mut timers: ResMut<PowerUpTimers>,
+    sim_time: Res<SimTime>,
+    mut player_query: Query<(Entity, &mut Sprite, Option<&Armor>), With<Player>>,
+) {
+    let (entity, mut sprite, armor) = match player_query.get_single_mut() {
+        Ok(player) => player,
+        Err(_) => {
+            // only borrow mutably when there is something to drop
+            if !timers.0.is_empty() {
+                timers.0.clear();
+            }
+            return;
+        }
+    };
+
+    let shielded = timers.is_active(PowerUpKind::Shield, sim_time.elapsed);
+    match (shielded, armor) {
+        (true, None) => {
+            commands.entity(entity).insert(SHIELD_ARMOR);
+            sprite.color = SHIELD_COLOR;
+        }
+        (false, Some(_)) => {
+            commands.entity(entity).remove::<Armor>();
+            sprite.color = Color::WHITE;
+        }
+        _ => {}
+    }
+}
@@ -0,0 +1,416 @@
+use std::{
+    fmt::Write as _,
+    fs,
+    io::{self, ErrorKind},
+    path::Path,
+};
+
+use bevy::{app::AppExit, prelude::*};
+
+use crate::{
+    config::GameConfig, gamepad::STICK_STEPS, in_game, AppState, GameMode, GameState, PlayerInput,
+    SimLabel, SimulationStage, PLAYER_MOVE_BAND, TIME_STEP,
+};
+
+const REPLAY_HEADER: &str = "invaders-replay 1";
+
+const INPUT_LEFT: u8 = 1;
+const INPUT_RIGHT: u8 = 1 << 1;
+const INPUT_FIRE: u8 = 1 << 2;
+const INPUT_UP: u8 = 1 << 3;
+const INPUT_DOWN: u8 = 1 << 4;
+const INPUT_FIRE_HELD: u8 = 1 << 5;
+const INPUT_SWITCH_WEAPON: u8 = 1 << 6;
+
+/// A recorded match: everything needed to run the simulation again, tick for tick.
+///
+/// Saved as a small text file, e.g.
+/// ```text
+/// invaders-replay 1
+/// seed 42
+/// size 598 676
+/// mode classic
+/// level levels/first.level.ron
+/// move-band 0.5
+/// score 35
+/// inputs 120*0 1*4 30*2 12*0@50,-100
+/// ```
+/// where `inputs` is the run-length encoded `PlayerInput` of every simulation tick: the ticks, the
+/// input bits, and the stick in `STICK_STEPS` after the `@` when it is tilted,
+/// and `mode`, `level` and `move-band` are only there when the match was not the default one.
+#[derive(Clone, Debug, Default)]
+pub struct Replay {
+    pub seed: u64,
+    pub width: f32,
+    pub height: f32,
+    pub mode: GameMode,
+    pub level: Option<String>,
+    pub move_band: Option<f32>,
+    pub score: Option<u32>, // final score, when the recording ran to the end
+    inputs: Vec<(u32, Sample)>, // (ticks, input)
+}
+
+impl Replay {
+    /// An empty recording of a game in `mode`, which the menu may have changed from the config's.
+    pub fn new(config: &GameConfig, mode: GameMode) -> Self {
+        Self {
+            seed: config.seed,
+            width: config.width,
+            height: config.height,
+            mode,
+            level: config.level.clone(),
+            move_band: Some(config.move_band).filter(|band| *band != PLAYER_MOVE_BAND),
+            ..Default::default()
+        }
+    }
+
+    /// Makes the config match the one the replay was recorded with.
+    pub fn configure(&self, config: &mut GameConfig) {
+        config.seed = self.seed;
+        config.width = self.width;
+        config.height = self.height;
+        config.mode = self.mode;
+        config.level = self.level.clone();
+        config.move_band = self.move_band.unwrap_or(PLAYER_MOVE_BAND);
+        // the playback ends the run, `ticks` only guards against it never starting
+        config.ticks = self.ticks() + (10. / TIME_STEP) as u64;
+    }
+
+    pub fn ticks(&self) -> u64 {
+        self.inputs.iter().map(|&(ticks, _)| ticks as u64).sum()
+    }
+
+    pub fn push(&mut self, input: &PlayerInput) {
+        let sample = encode(input);
+        match self.inputs.last_mut() {
+            Some((ticks, last)) if *last == sample => *ticks += 1,
+            _ => self.inputs.push((1, sample)),
+        }
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        Self::parse(&fs::read_to_string(path)?)
+            .map_err(|err| io::Error::new(ErrorKind::InvalidData, err))
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        fs::write(path, self.to_string())
+    }
+
+    fn parse(text: &str) -> Result<Self, String> {
+        let mut lines = text.lines();
+        if lines.next() != Some(REPLAY_HEADER) {
+            return Err(format!("expected `{}` header", REPLAY_HEADER));
+        }
+
+        let mut replay = Replay::default();
+        for line in lines {
+            let (key, value) = line.split_once(' ').unwrap_or((line, ""));
+            match key {
+                "seed" => replay.seed = parse_field(key, value)?,
+                "size" => {
+                    let (width, height) = value.split_once(' ').unwrap_or((value, ""));
+                    replay.width = parse_field(key, width)?;
+                    replay.height = parse_field(key, height)?;
+                }
+                "mode" => replay.mode = parse_field(key, value)?,
+                "level" => replay.level = Some(value.to_string()),
+                "move-band" => replay.move_band = Some(parse_field(key, value)?),
+                "score" => replay.score = Some(parse_field(key, value)?),
+                "inputs" => {
+                    for run in value.split_whitespace() {
+                        let (ticks, input) = run.split_once('*').unwrap_or((run, ""));
+                        let (bits, stick) = input.split_once('@').unwrap_or((input, "0,0"));
+                        let (x, y) = stick.split_once(',').unwrap_or((stick, ""));
+                        let sample = (
+                            parse_field(key, bits)?,
+                            [parse_field(key, x)?, parse_field(key, y)?],
+                        );
+                        replay.inputs.push((parse_field(key, ticks)?, sample));
+                    }
+                }
+                "" => {}
+                _ => return Err(format!("unknown replay field `{}`", key)),
+            }
+        }
+
+        Ok(replay)
+    }
+}
+
+impl std::fmt::Display for Replay {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "{}", REPLAY_HEADER)?;
+        writeln!(f, "seed {}", self.seed)?;
+        writeln!(f, "size {} {}", self.width, self.height)?;
+        if self.mode != GameMode::Waves {
+            writeln!(f, "mode {}", self.mode.name())?;
+        }
+        if let Some(level) = &self.level {
+            writeln!(f, "level {}", level)?;
+        }
+        if let Some(move_band) = self.move_band {
+            writeln!(f, "move-band {}", move_band)?;
+        }
+        if let Some(score) = self.score {
+            writeln!(f, "score {}", score)?;
+        }
+        let mut inputs = String::from("inputs");
+        for (ticks, (bits, [x, y])) in &self.inputs {
+            write!(inputs, " {}*{}", ticks, bits)?;
+            if (*x, *y) != (0, 0) {
+                write!(inputs, "@{},{}", x, y)?;
+            }
+        }
+        writeln!(f, "{}", inputs)
+    }
+}
+
+fn parse_field<T: std::str::FromStr>(key: &str, value: &str) -> Result<T, String> {
+    value
+        .parse()
+        .map_err(|_| format!("invalid value `{}` for replay field `{}`", value, key))
+}
+
+/// Input of one tick as it is recorded: the input bits, and the stick in `STICK_STEPS`
+type Sample = (u8, [i8; 2]);
+
+fn encode(input: &PlayerInput) -> Sample {
+    let mut bits = 0;
+    if input.left {
+        bits |= INPUT_LEFT;
+    }
+    if input.right {
+        bits |= INPUT_RIGHT;
+    }
+    if input.fire {
+        bits |= INPUT_FIRE;
+    }
+    if input.up {
+        bits |= INPUT_UP;
+    }
+    if input.down {
+        bits |= INPUT_DOWN;
+    }
+    if input.fire_held {
+        bits |= INPUT_FIRE_HELD;
+    }
+    if input.switch_weapon {
+        bits |= INPUT_SWITCH_WEAPON;
+    }
+    // the stick is already in steps, so this is exact
+    let step = |tilt: f32| (tilt * STICK_STEPS).round() as i8;
+    (bits, [step(input.stick.x), step(input.stick.y)])
+}
+
+fn decode((bits, [x, y]): Sample) -> PlayerInput {
+    PlayerInput {
+        left: bits & INPUT_LEFT != 0,
+        right: bits & INPUT_RIGHT != 0,
+        fire: bits & INPUT_FIRE != 0,
+        up: bits & INPUT_UP != 0,
+        down: bits & INPUT_DOWN != 0,
+        stick: Vec2::new(x as f32, y as f32) / STICK_STEPS,
+        fire_held: bits & INPUT_FIRE_HELD != 0,
+        switch_weapon: bits & INPUT_SWITCH_WEAPON != 0,
+    }
+}
+
+// region: --- Resources
+
+/// Resource - Replay being played back
+pub struct ReplayPlayer {
+    replay: Replay,
+    run: usize,    // index into the replay inputs
+    run_tick: u32, // ticks already played of that run
+}
+
+impl ReplayPlayer {
+    pub fn new(replay: Replay) -> Self {
+        Self {
+            replay,
+            run: 0,
+            run_tick: 0,
+        }
+    }
+
+    fn next_input(&mut self) -> Option<PlayerInput> {
+        let &(ticks, sample) = self.replay.inputs.get(self.run)?;
+        self.run_tick += 1;
+        if self.run_tick >= ticks {
+            self.run += 1;
+            self.run_tick = 0;
+        }
+        Some(decode(sample))
+    }
+}
+
+/// Resource - Replay being recorded
+pub struct ReplayRecorder(pub Replay);
+
+// endregion: --- Resources
+
+/// Records and plays back the per-tick `PlayerInput`.
+/// Plays back the `ReplayPlayer` resource if there is one, and records every game when `--record` is given.
+pub struct ReplayPlugin;
+
+impl Plugin for ReplayPlugin {
+    fn build(&self, app: &mut App) {
+        let config = app.world.resource::<GameConfig>();
+        let (recording, headless) = (config.record.is_some(), config.headless);
+
+        if app.world.contains_resource::<ReplayPlayer>() {
+            app.add_system_set_to_stage(
+                SimulationStage,
+                SystemSet::new()
+                    .with_run_criteria(in_game)
+                    .label(SimLabel::Clock)
+                    .with_system(replay_playback_system),
+            );
+            // headless runs report the result themselves
+            if !headless {
+                app.add_system_to_stage(CoreStage::Last, replay_report_system);
+            }
+        }
+
+        if recording {
+            // playback writes the input before it gets recorded, so `--replay` and `--record`
+            // together re-record an existing replay
+            app.add_system_set(
+                SystemSet::on_enter(AppState::InGame).with_system(replay_record_start_system),
+            )
+            .add_system_set_to_stage(
+                SimulationStage,
+                SystemSet::new()
+                    .with_run_criteria(in_game)
+                    .label(SimLabel::Input)
+                    .after(SimLabel::Spawn)
+                    .before(SimLabel::Fire)
+                    .with_system(replay_record_system),
+            );
+            // headless runs save once they are done
+            if !headless {
+                app.add_system_set(
+                    SystemSet::on_exit(AppState::InGame).with_system(replay_record_end_system),
+                )
+                .add_system_to_stage(CoreStage::Last, replay_save_system);
+            }
+        }
+    }
+}
+
+/// Feeds the recorded input to the simulation and ends the game when it runs out.
+fn replay_playback_system(
+    mut player: ResMut<ReplayPlayer>,
+    mut input: ResMut<PlayerInput>,
+    mut exit: EventWriter<AppExit>,
+) {
+    match player.next_input() {
+        Some(next) => *input = next,
+        None => {
+            *input = PlayerInput::default();
+            exit.send(AppExit);
+        }
+    }
+}
+
+fn replay_report_system(
+    mut exit: EventReader<AppExit>,
+    player: Res<ReplayPlayer>,
+    game_state: Res<GameState>,
+) {
+    if exit.iter().next().is_some() {
+        println!(
+            "replay finished: {:?}, recorded score: {:?}",
+            game_state, player.replay.score
+        );
+    }
+}
+
+/// Every game gets its own recording, the file keeps the last one.
+fn replay_record_start_system(
+    mut commands: Commands,
+    config: Res<GameConfig>,
+    mode: Res<GameMode>,
+) {
+    commands.insert_resource(ReplayRecorder(Replay::new(&config, *mode)));
+}
+
+fn replay_record_system(recorder: Option<ResMut<ReplayRecorder>>, input: Res<PlayerInput>) {
+    if let Some(mut recorder) = recorder {
+        recorder.0.push(&input);
+    }
+}
+
+fn replay_record_end_system(
+    mut commands: Commands,
+    recorder: Option<Res<ReplayRecorder>>,
+    game_state: Res<GameState>,
+    config: Res<GameConfig>,
+) {
+    if let Some(recorder) = recorder {
+        save_recording(&recorder, &game_state, &config);
+        commands.remove_resource::<ReplayRecorder>();
+    }
+}
+
+/// Saves the game in progress when the app is closed.
+fn replay_save_system(
+    mut exit: EventReader<AppExit>,
+    recorder: Option<Res<ReplayRecorder>>,
+    game_state: Res<GameState>,
+    config: Res<GameConfig>,
+) {
+    if let (Some(recorder), Some(_)) = (recorder, exit.iter().next()) {
+        save_recording(&recorder, &game_state, &config);
+    }
+}
+
+/// Writes the recording, together with the final score, to the `--record` path.
+pub fn save_recording(recorder: &ReplayRecorder, game_state: &GameState, config: &GameConfig) {
+    let path = match &config.record {
+        Some(path) => path,
+        None => return,
+    };
+
+    let mut replay = recorder.0.clone();
+    replay.score = Some(game_state.score);
+    match replay.save(path) {
+        Ok(()) => println!("replay of seed {} saved to {}", replay.seed, path.display()),
+        Err(err) => eprintln!(
+            "error: could not save replay to {}: {}",
+            path.display(),
+            err
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_stick_survives_a_save() {
+        let mut replay = Replay::default();
+        replay.push(&PlayerInput {
+            right: true,
+            ..Default::default()
+        });
+        for _ in 0..3 {
+            replay.push(&PlayerInput {
+                stick: Vec2::new(0.37, -1.),
+                fire: true,
+                ..Default::default()
+            });
+        }
+
+        let text = replay.to_string();
+        assert!(text.ends_with("inputs 1*2 3*4@37,-100\n"), "{}", text);
+
+        let mut player = ReplayPlayer::new(Replay::parse(&text).unwrap());
+        let first = player.next_input().unwrap();
+        assert!(first.right && first.stick == Vec2::ZERO);
+        let second = player.next_input().unwrap();
+        assert!(second.fire && second.stick == Vec2::new(0.37, -1.));
+    }
+}
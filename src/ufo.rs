@@ -0,0 +1,308 @@
+use bevy::{math::Vec3Swizzles, prelude::*, sprite::collide_aabb::collide};
+use bevy_kira_audio::{Audio, AudioApp, AudioChannel};
+use rand::Rng;
+
+use crate::{
+    components::{ExplosionToSpawn, FromPlayer, Laser, Movable, SpriteSize, Velocity},
+    enemy::{LevelRunner, Wave},
+    in_game,
+    weapon::Piercing,
+    AppState, GameFonts, GameMode, GameRng, GameSounds, GameState, GameTextures, PlayerState,
+    SimLabel, SimTime, SimulationStage, WinSize, UFO_SIZE,
+};
+
+const UFO_INTERVAL: (f64, f64) = (20., 35.); // seconds between two UFOs, unless the level schedules them
+const UFO_SPEED: f32 = 0.25; // multiplier of the base speed
+const UFO_TOP: f32 = 70.; // from the top of the window, above the classic grid
+const UFO_SCALE: f32 = 0.35;
+const UFO_COLOR: Color = Color::rgb(1., 0.3, 0.3);
+const UFO_EXPLOSION_SCALE: f32 = 0.8;
+/// Bonus of a hit by the shot number `n`, at `n % 15`, as in the arcade game: the 23rd shot and
+/// every 15th after it score 300.
+const UFO_BONUS: [u32; 15] = [
+    100, 50, 50, 100, 150, 100, 100, 50, 300, 100, 100, 100, 50, 150, 100,
+];
+const FLOATING_SCORE_TIME: f64 = 1.; // seconds a bonus floats on screen
+const FLOATING_SCORE_SPEED: f32 = 0.08; // multiplier of the base speed
+
+/// Audio channel of the UFO loop, so stopping it leaves the other sounds playing
+pub struct UfoChannel;
+
+// region: --- Components
+
+/// Component - Mystery ship crossing the top of the screen, worth a bonus
+#[derive(Component)]
+pub struct Ufo;
+
+/// Component - Score floating up from where it was earned, until the sim time
+#[derive(Component)]
+struct FloatingScore {
+    until: f64,
+}
+
+// endregion: --- Components
+
+// region: --- Resources
+
+/// Resource - When the next UFO flies
+#[derive(Default)]
+struct UfoSchedule {
+    next_at: Option<f64>, // sim time of the next random UFO, drawn once the last one flew
+    wave: u32,            // wave the scripted UFOs below belong to
+    scripted: usize,      // scripted UFOs of the wave flown so far
+    flying: bool,         // is the loop playing?
+}
+
+// endregion: --- Resources
+
+/// The UFO flies at random intervals, or when the level schedules it, and does not count
+/// towards the enemies of the wave.
+pub struct UfoPlugin;
+
+impl Plugin for UfoPlugin {
+    fn build(&self, app: &mut App) {
+        // headless, the channel is there to drain
+        if app.world.contains_resource::<AssetServer>() {
+            app.add_audio_channel::<UfoChannel>();
+        }
+
+        app.insert_resource(UfoSchedule::default())
+            .add_system_set(SystemSet::on_enter(AppState::InGame).with_system(ufo_reset_system))
+            .add_system_set(SystemSet::on_exit(AppState::InGame).with_system(ufo_cleanup_system))
+            .add_system_set(
+                SystemSet::on_pause(AppState::InGame).with_system(ufo_sound_pause_system),
+            )
+            .add_system_set(
+                SystemSet::on_resume(AppState::InGame).with_system(ufo_sound_resume_system),
+            )
+            .add_system_set_to_stage(
+                SimulationStage,
+                SystemSet::new()
+                    .with_run_criteria(in_game)
+                    .label(SimLabel::Spawn)
+                    .after(SimLabel::Clock)
+                    .with_system(ufo_spawn_system),
+            )
+            .add_system_set_to_stage(
+                SimulationStage,
+                SystemSet::new()
+                    .with_run_criteria(in_game)
+                    .label(SimLabel::Collision)
+                    .after(SimLabel::Movement)
+                    .with_system(ufo_hit_system),
+            )
+            .add_system_set_to_stage(
+                SimulationStage,
+                SystemSet::new()
+                    .with_run_criteria(in_game)
+                    .label(SimLabel::Effects)
+                    .after(SimLabel::Collision)
+                    .with_system(floating_score_system),
+            );
+    }
+}
+
+fn ufo_reset_system(mut schedule: ResMut<UfoSchedule>) {
+    *schedule = UfoSchedule::default();
+}
+
+fn ufo_cleanup_system(
+    mut commands: Commands,
+    ufo_channel: Res<AudioChannel<UfoChannel>>,
+    query: Query<Entity, Or<(With<Ufo>, With<FloatingScore>)>>,
+) {
+    for entity in query.iter() {
+        commands.entity(entity).despawn();
+    }
+    ufo_channel.stop();
+}
+
+fn ufo_sound_pause_system(ufo_channel: Res<AudioChannel<UfoChannel>>) {
+    ufo_channel.pause();
+}
+
+fn ufo_sound_resume_system(ufo_channel: Res<AudioChannel<UfoChannel>>) {
+    ufo_channel.resume();
+}
+
+/// Sends a UFO across when the level schedules one (seconds into the wave), or else at random,
+/// from the left after an even number of shots and from the right after an odd one.
+fn ufo_spawn_system(
+    mut commands: Commands,
+    mut schedule: ResMut<UfoSchedule>,
+    mut rng: ResMut<GameRng>,
+    game_mode: Res<GameMode>,
+    level_runner: Res<LevelRunner>,
+    wave: Res<Wave>,
+    player_state: Res<PlayerState>,
+    sim_time: Res<SimTime>,
+    win_size: Res<WinSize>,
+    game_textures: Res<GameTextures>,
+    game_sounds: Res<GameSounds>,
+    ufo_channel: Res<AudioChannel<UfoChannel>>,
+    query: Query<(), With<Ufo>>,
+) {
+    // the loop plays for as long as a UFO is on screen
+    if schedule.flying && query.is_empty() {
+        ufo_channel.stop();
+        schedule.flying = false;
+    }
+
+    let now = sim_time.elapsed;
+    if schedule.wave != wave.number {
+        schedule.wave = wave.number;
+        schedule.scripted = 0;
+    }
+
+    let due = if level_runner.is_active() && *game_mode != GameMode::Classic {
+        let at = level_runner
+            .wave(wave.number)
+            .and_then(|level_wave| level_wave.ufos.get(schedule.scripted).copied());
+        let due = !wave.in_intermission() && at.is_some_and(|at| now >= wave.started_at + at);
+        schedule.scripted += due as usize;
+        due
+    } else {
+        let next_at = *schedule
+            .next_at
+            .get_or_insert_with(|| now + rng.0.gen_range(UFO_INTERVAL.0..UFO_INTERVAL.1));
+        let due = now >= next_at;
+        if due {
+            schedule.next_at = None;
+        }
+        due
+    };
+
+    // one at a time, a UFO due while another one flies is skipped
+    if !due || !query.is_empty() {
+        return;
+    }
+
+    let dir = if player_state.shots.is_multiple_of(2) {
+        1.
+    } else {
+        -1.
+    };
+    let x = -dir * (win_size.width / 2. + UFO_SIZE.0 * UFO_SCALE);
+    let y = win_size.height / 2. - UFO_TOP;
+
+    commands
+        .spawn_bundle(SpriteBundle {
+            texture: game_textures.ufo.clone(),
+            sprite: Sprite {
+                color: UFO_COLOR,
+                ..Default::default()
+            },
+            transform: Transform {
+                translation: Vec3::new(x, y, 10.),
+                scale: Vec3::new(UFO_SCALE, UFO_SCALE, 1.),
+                ..Default::default()
+            },
+            ..Default::default()
+        })
+        .insert(Ufo)
+        .insert(SpriteSize::from(UFO_SIZE))
+        .insert(Movable { auto_despawn: true })
+        .insert(Velocity {
+            x: dir * UFO_SPEED,
+            y: 0.,
+        });
+
+    ufo_channel.play_looped(game_sounds.ufo.clone());
+    schedule.flying = true;
+}
+
+fn ufo_hit_system(
+    mut commands: Commands,
+    mut game_state: ResMut<GameState>,
+    player_state: Res<PlayerState>,
+    sim_time: Res<SimTime>,
+    laser_query: Query<
+        (Entity, &Transform, &SpriteSize, Option<&Piercing>),
+        (With<Laser>, With<FromPlayer>),
+    >,
+    ufo_query: Query<(Entity, &Transform, &SpriteSize), With<Ufo>>,
+    game_fonts: Res<GameFonts>,
+    game_sounds: Res<GameSounds>,
+    audio: Res<Audio>,
+) {
+    for (ufo_entity, ufo_tf, ufo_size) in ufo_query.iter() {
+        for (laser_entity, laser_tf, laser_size, piercing) in laser_query.iter() {
+            let collision = collide(
+                laser_tf.translation,
+                laser_size.0 * laser_tf.scale.xy(),
+                ufo_tf.translation,
+                ufo_size.0 * ufo_tf.scale.xy(),
+            );
+            if collision.is_none() {
+                continue;
+            }
+
+            // a beam goes on through
+            if piercing.is_none() {
+                commands.entity(laser_entity).despawn();
+            }
+            commands.entity(ufo_entity).despawn();
+            commands
+                .spawn()
+                .insert(ExplosionToSpawn(ufo_tf.translation, UFO_EXPLOSION_SCALE));
+            audio.play(game_sounds.enemy_explosion.clone());
+
+            let bonus = UFO_BONUS[player_state.shots as usize % UFO_BONUS.len()];
+            game_state.score += bonus;
+            spawn_floating_score(
+                &mut commands,
+                &game_fonts,
+                ufo_tf.translation,
+                bonus,
+                sim_time.elapsed,
+            );
+            break;
+        }
+    }
+}
+
+fn spawn_floating_score(
+    commands: &mut Commands,
+    game_fonts: &GameFonts,
+    translation: Vec3,
+    score: u32,
+    now: f64,
+) {
+    commands
+        .spawn_bundle(Text2dBundle {
+            text: Text::with_section(
+                score.to_string(),
+                TextStyle {
+                    font: game_fonts.hud.clone(),
+                    font_size: 16.,
+                    color: Color::YELLOW,
+                },
+                TextAlignment {
+                    vertical: VerticalAlign::Center,
+                    horizontal: HorizontalAlign::Center,
+                },
+            ),
+            transform: Transform::from_translation(translation.truncate().extend(20.)),
+            ..Default::default()
+        })
+        .insert(FloatingScore {
+            until: now + FLOATING_SCORE_TIME,
+        })
+        .insert(Movable { auto_despawn: true })
+        .insert(Velocity {
+            x: 0.,
+            y: FLOATING_SCORE_SPEED,
+        });
+}
+
+fn floating_score_system(
+    mut commands: Commands,
+    sim_time: Res<SimTime>,
+    query: Query<(Entity, &FloatingScore)>,
+) {
+    for (entity, floating_score) in query.iter() {
+        if sim_time.elapsed >= floating_score.until {
+            commands.entity(entity).despawn();
+        }
+    }
+}
@@ -0,0 +1,275 @@
+use bevy::{
+    math::{Mat2, Vec3Swizzles},
+    prelude::*,
+};
+
+use crate::{
+    components::{Damage, Enemy, FromPlayer, Laser, Movable, SpriteSize, Velocity},
+    enemy::{bullet_rotation, steer},
+    GameTextures, SimTime, SPRITE_SCALE, TIME_STEP,
+};
+
+const MUZZLE_HEIGHT: f32 = 15.; // above the center of the player
+const TWIN_OFFSET: f32 = 31.; // sideways from the center of the player, at the edges of the sprite
+
+/// Where a projectile of a volley leaves the player: sideways offset and angle from straight up (radians)
+#[derive(Clone, Copy, Debug)]
+pub struct Muzzle(pub f32, pub f32);
+
+/// Definition of a player weapon, see `WeaponKind::def`
+pub struct WeaponDef {
+    pub name: &'static str,
+    pub sprite: &'static str,
+    pub sound: &'static str,
+    pub size: (f32, f32),  // of the sprite image
+    pub scale: (f32, f32), // a beam is stretched
+    pub interval: f64,     // seconds between two volleys
+    pub speed: f32,        // multiplier of the base speed
+    pub damage: f32,
+    pub muzzles: &'static [Muzzle],
+    pub piercing: bool,    // goes through the enemies it hits, damaging each once
+    pub seek: Option<f32>, // turns towards the nearest enemy at up to this many radians per second
+}
+
+const WEAPONS: [WeaponDef; 6] = [
+    WeaponDef {
+        name: "single",
+        sprite: "laser_a_01.png",
+        sound: "sounds/player_laser.wav",
+        size: (9., 54.),
+        scale: (SPRITE_SCALE, SPRITE_SCALE),
+        interval: 0.25,
+        speed: 1.2,
+        damage: 15.,
+        muzzles: &[Muzzle(0., 0.)],
+        piercing: false,
+        seek: None,
+    },
+    WeaponDef {
+        name: "twin",
+        sprite: "laser_twin_01.png",
+        sound: "sounds/twin.wav",
+        size: (7., 40.),
+        scale: (SPRITE_SCALE * 1.3, SPRITE_SCALE * 1.3),
+        interval: 0.2,
+        speed: 1.,
+        damage: 10.,
+        muzzles: &[Muzzle(-TWIN_OFFSET, 0.), Muzzle(TWIN_OFFSET, 0.)],
+        piercing: false,
+        seek: None,
+    },
+    WeaponDef {
+        name: "spread",
+        sprite: "pellet_01.png",
+        sound: "sounds/spread.wav",
+        size: (12., 12.),
+        scale: (SPRITE_SCALE * 1.2, SPRITE_SCALE * 1.2),
+        interval: 0.35,
+        speed: 0.9,
+        damage: 8.,
+        muzzles: &[
+            Muzzle(-10., 0.3),
+            Muzzle(-5., 0.15),
+            Muzzle(0., 0.),
+            Muzzle(5., -0.15),
+            Muzzle(10., -0.3),
+        ],
+        piercing: false,
+        seek: None,
+    },
+    WeaponDef {
+        name: "rapid",
+        sprite: "laser_rapid_01.png",
+        sound: "sounds/rapid.wav",
+        size: (5., 36.),
+        scale: (SPRITE_SCALE * 1.2, SPRITE_SCALE * 1.2),
+        interval: 0.08,
+        speed: 1.4,
+        damage: 5.,
+        muzzles: &[Muzzle(0., 0.)],
+        piercing: false,
+        seek: None,
+    },
+    WeaponDef {
+        name: "beam",
+        sprite: "beam_01.png",
+        sound: "sounds/beam.wav",
+        size: (15., 64.),
+        scale: (SPRITE_SCALE * 0.9, SPRITE_SCALE * 2.5),
+        interval: 0.5,
+        speed: 2.,
+        damage: 12.,
+        muzzles: &[Muzzle(0., 0.)],
+        piercing: true,
+        seek: None,
+    },
+    WeaponDef {
+        name: "missiles",
+        sprite: "missile_01.png",
+        sound: "sounds/missile.wav",
+        size: (13., 40.),
+        scale: (SPRITE_SCALE * 1.1, SPRITE_SCALE * 1.1),
+        interval: 0.6,
+        speed: 0.6,
+        damage: 25.,
+        muzzles: &[Muzzle(-TWIN_OFFSET, 0.4), Muzzle(TWIN_OFFSET, -0.4)],
+        piercing: false,
+        seek: Some(3.),
+    },
+];
+
+// region: --- Components
+
+/// Component - Type of a player weapon, the registry of weapons is `WEAPONS`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Component)]
+pub enum WeaponKind {
+    Single,
+    Twin,
+    Spread,
+    Rapid,
+    Beam,
+    Missiles,
+}
+
+impl WeaponKind {
+    pub const ALL: [WeaponKind; 6] = [
+        WeaponKind::Single,
+        WeaponKind::Twin,
+        WeaponKind::Spread,
+        WeaponKind::Rapid,
+        WeaponKind::Beam,
+        WeaponKind::Missiles,
+    ];
+
+    pub fn def(&self) -> &'static WeaponDef {
+        &WEAPONS[*self as usize]
+    }
+
+    /// The weapon after this one, switching around
+    pub fn next(&self) -> WeaponKind {
+        Self::ALL[(*self as usize + 1) % Self::ALL.len()]
+    }
+
+    /// The weapon a weapon upgrade turns this one into, the last one stays
+    pub fn upgraded(&self) -> WeaponKind {
+        Self::ALL[(*self as usize + 1).min(Self::ALL.len() - 1)]
+    }
+}
+
+/// Component - Weapon of the player, and when it can fire again
+#[derive(Component)]
+pub struct Weapon {
+    pub kind: WeaponKind,
+    pub next_shot: f64, // sim time
+}
+
+impl Weapon {
+    pub fn new(kind: WeaponKind) -> Self {
+        Self {
+            kind,
+            next_shot: 0.,
+        }
+    }
+
+    /// Switches to the weapon, which still waits for the reload of the last one.
+    pub fn switch_to(&mut self, kind: WeaponKind) {
+        self.kind = kind;
+    }
+
+    /// How far along the reload is at sim time `now`, from 0 (just fired) to 1 (can fire)
+    pub fn reloaded(&self, now: f64) -> f32 {
+        (1. - (self.next_shot - now) / self.kind.def().interval).clamp(0., 1.) as f32
+    }
+}
+
+/// Component - Player projectile going through the enemies, with the ones it already damaged
+#[derive(Component, Default)]
+pub struct Piercing {
+    pub hit: Vec<Entity>,
+}
+
+/// Component - Player missile turning towards the nearest enemy, at up to `turn` radians per second
+#[derive(Component)]
+pub struct Seeker {
+    turn: f32,
+}
+
+// endregion: --- Components
+
+/// Fires a volley of the weapon from the player at `origin`, and returns the sim time it can fire again.
+pub fn fire_weapon(
+    commands: &mut Commands,
+    game_textures: &GameTextures,
+    sim_time: &SimTime,
+    kind: WeaponKind,
+    origin: Vec2,
+) -> f64 {
+    let def = kind.def();
+    let texture = game_textures
+        .weapons
+        .get(&kind)
+        .cloned()
+        .unwrap_or_default();
+
+    for &Muzzle(x_offset, angle) in def.muzzles {
+        let velocity = Mat2::from_angle(angle) * Vec2::new(0., def.speed);
+        let projectile = commands
+            .spawn_bundle(SpriteBundle {
+                texture: texture.clone(),
+                transform: Transform {
+                    translation: (origin + Vec2::new(x_offset, MUZZLE_HEIGHT)).extend(0.),
+                    rotation: Quat::from_rotation_z(angle),
+                    scale: Vec3::new(def.scale.0, def.scale.1, 1.),
+                },
+                ..Default::default()
+            })
+            .insert(Laser)
+            .insert(FromPlayer)
+            .insert(Damage(def.damage))
+            .insert(SpriteSize::from(def.size))
+            .insert(Movable { auto_despawn: true })
+            .insert(Velocity {
+                x: velocity.x,
+                y: velocity.y,
+            })
+            .id();
+
+        if def.piercing {
+            commands.entity(projectile).insert(Piercing::default());
+        }
+        if let Some(turn) = def.seek {
+            commands.entity(projectile).insert(Seeker { turn });
+        }
+    }
+
+    sim_time.elapsed + def.interval
+}
+
+/// Turns the player missiles towards the nearest enemy, as fast as they can turn.
+pub fn seeker_system(
+    enemy_query: Query<&Transform, (With<Enemy>, Without<Seeker>)>,
+    mut query: Query<(&mut Transform, &mut Velocity, &Seeker)>,
+) {
+    for (mut transform, mut velocity, seeker) in query.iter_mut() {
+        let position = transform.translation.xy();
+        let target = enemy_query
+            .iter()
+            .map(|enemy_tf| enemy_tf.translation.xy())
+            .min_by(|a, b| {
+                a.distance_squared(position)
+                    .total_cmp(&b.distance_squared(position))
+            });
+        let target = match target {
+            Some(target) => target,
+            None => continue,
+        };
+
+        let turned = steer(
+            Vec2::new(velocity.x, velocity.y),
+            target - position,
+            seeker.turn * TIME_STEP,
+        );
+        (velocity.x, velocity.y) = turned.into();
+        transform.rotation = bullet_rotation(turned);
+    }
+}
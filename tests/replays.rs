@@ -0,0 +1,49 @@
+//! Plays every recording in `replays/` headless, and checks it still scores what it recorded.
+
+use std::{fs, path::Path, process::Command};
+
+fn recorded_score(replay: &str) -> Option<u32> {
+    replay
+        .lines()
+        .find_map(|line| line.strip_prefix("score "))
+        .and_then(|score| score.trim().parse().ok())
+}
+
+#[test]
+fn replays_score_what_they_recorded() {
+    let mut replays: Vec<_> = fs::read_dir(Path::new(env!("CARGO_MANIFEST_DIR")).join("replays"))
+        .expect("replays directory")
+        .map(|entry| entry.expect("replay entry").path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "replay"))
+        .collect();
+    replays.sort();
+    assert!(!replays.is_empty(), "no replays to play");
+
+    for path in replays {
+        let replay = fs::read_to_string(&path).expect("readable replay");
+        let score = recorded_score(&replay)
+            .unwrap_or_else(|| panic!("{} records no score", path.display()));
+
+        let output = Command::new(env!("CARGO_BIN_EXE_invaders"))
+            .current_dir(env!("CARGO_MANIFEST_DIR"))
+            .arg("--headless")
+            .arg("--replay")
+            .arg(&path)
+            .output()
+            .expect("invaders runs");
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        assert!(
+            output.status.success(),
+            "{} failed: {}",
+            path.display(),
+            String::from_utf8_lossy(&output.stderr)
+        );
+        assert!(
+            stdout.contains(&format!("GameState {{ score: {} }}", score)),
+            "{} did not score {}: {}",
+            path.display(),
+            score,
+            stdout
+        );
+    }
+}